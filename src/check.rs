@@ -0,0 +1,191 @@
+//! Nagios/Icinga check plugin output.
+//!
+//! A check prints a single status line with performance data and exits with
+//! the state: 0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde::Deserialize;
+
+use crate::report::{HealthReport, HealthStatus, CRITICAL_LIFE_PERCENT, WARNING_LIFE_PERCENT};
+
+/// Alert thresholds, unset ones are not checked. Remaining life and spare
+/// blocks alert at or below their threshold, grown bad blocks and the average
+/// erase count at or above.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Thresholds {
+    pub warn_life: Option<f32>,
+    pub crit_life: Option<f32>,
+    pub warn_grown_bad: Option<u64>,
+    pub crit_grown_bad: Option<u64>,
+    pub warn_spare: Option<u64>,
+    pub crit_spare: Option<u64>,
+    pub warn_erase_avg: Option<u64>,
+    pub crit_erase_avg: Option<u64>,
+}
+
+impl Thresholds {
+    /// Built-in thresholds, matching the [`HealthStatus`](crate::report::HealthStatus)
+    /// classification.
+    pub fn defaults() -> Self {
+        return Thresholds {
+            warn_life: Some(WARNING_LIFE_PERCENT),
+            crit_life: Some(CRITICAL_LIFE_PERCENT),
+            ..Default::default()
+        };
+    }
+
+    /// Thresholds set in `other` replace ours.
+    pub fn merge(self, other: &Thresholds) -> Self {
+        return Thresholds {
+            warn_life: other.warn_life.or(self.warn_life),
+            crit_life: other.crit_life.or(self.crit_life),
+            warn_grown_bad: other.warn_grown_bad.or(self.warn_grown_bad),
+            crit_grown_bad: other.crit_grown_bad.or(self.crit_grown_bad),
+            warn_spare: other.warn_spare.or(self.warn_spare),
+            crit_spare: other.crit_spare.or(self.crit_spare),
+            warn_erase_avg: other.warn_erase_avg.or(self.warn_erase_avg),
+            crit_erase_avg: other.crit_erase_avg.or(self.crit_erase_avg),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckState {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl CheckState {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            CheckState::Ok => 0,
+            CheckState::Warning => 1,
+            CheckState::Critical => 2,
+            CheckState::Unknown => 3,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CheckState::Ok => "OK",
+            CheckState::Warning => "WARNING",
+            CheckState::Critical => "CRITICAL",
+            CheckState::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// Outcome of a check, rendered as `SDMON <STATE> - <summary> | <perfdata>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub state: CheckState,
+    pub summary: String,
+    pub perfdata: Vec<String>,
+}
+
+impl CheckResult {
+    /// Result of a device that could not be read.
+    pub fn unknown(summary: &str) -> Self {
+        return CheckResult { state: CheckState::Unknown, summary: summary.to_string(), perfdata: Vec::new() };
+    }
+}
+
+impl Display for CheckResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "SDMON {} - {}", self.state.name(), self.summary)?;
+        if !self.perfdata.is_empty() {
+            write!(f, " | {}", self.perfdata.join(" "))?;
+        }
+        return Ok(());
+    }
+}
+
+fn threshold<T: Display>(value: Option<T>) -> String {
+    return value.map(|value| value.to_string()).unwrap_or_default();
+}
+
+/// State of `value` against thresholds that alert when `breached` holds.
+fn state_of<T: Copy>(value: T, warn: Option<T>, crit: Option<T>, breached: fn(T, T) -> bool) -> CheckState {
+    if crit.is_some_and(|crit| breached(value, crit)) {
+        return CheckState::Critical;
+    }
+    if warn.is_some_and(|warn| breached(value, warn)) {
+        return CheckState::Warning;
+    }
+    return CheckState::Ok;
+}
+
+/// Evaluates `report` against `thresholds`. Values the card does not report
+/// are skipped, UNKNOWN is returned when nothing could be checked. A health
+/// status worse than the remaining life alone gives, e.g. eMMC PRE_EOL_INFO
+/// Urgent, raises the state as well.
+pub fn evaluate(report: &HealthReport, thresholds: &Thresholds) -> CheckResult {
+    let mut state = CheckState::Ok;
+    let mut checked = false;
+    let mut problems = Vec::new();
+    let mut perfdata = Vec::new();
+    let t = thresholds;
+
+    let mut check = |name: &str, value: String, value_state: CheckState, is_checked: bool| {
+        checked |= is_checked;
+        if value_state != CheckState::Ok {
+            problems.push(format!("{} {}", name, value));
+        }
+        state = state.max(value_state);
+    };
+
+    if let Some(life) = report.remaining_life_percent {
+        let life_state = state_of(life, t.warn_life, t.crit_life, |value, limit| value <= limit);
+        check("life", format!("{}%", life), life_state, t.warn_life.is_some() || t.crit_life.is_some());
+        perfdata.push(format!("life={}%;{};{}", life, threshold(t.warn_life), threshold(t.crit_life)));
+    }
+    if let Some(grown) = report.bad_blocks.grown {
+        let grown_state = state_of(grown, t.warn_grown_bad, t.crit_grown_bad, |value, limit| value >= limit);
+        check("grown bad blocks", grown.to_string(), grown_state, t.warn_grown_bad.is_some() || t.crit_grown_bad.is_some());
+        perfdata.push(format!("grown_bad={};{};{}", grown, threshold(t.warn_grown_bad), threshold(t.crit_grown_bad)));
+    }
+    if let Some(spare) = report.spare_blocks {
+        let spare_state = state_of(spare, t.warn_spare, t.crit_spare, |value, limit| value <= limit);
+        check("spare blocks", spare.to_string(), spare_state, t.warn_spare.is_some() || t.crit_spare.is_some());
+        perfdata.push(format!("spare_blocks={};{};{}", spare, threshold(t.warn_spare), threshold(t.crit_spare)));
+    }
+    if let Some(avg) = report.erase_count.avg {
+        let avg_state = state_of(avg, t.warn_erase_avg, t.crit_erase_avg, |value, limit| value >= limit);
+        check("average erase count", avg.to_string(), avg_state, t.warn_erase_avg.is_some() || t.crit_erase_avg.is_some());
+        perfdata.push(format!("erase_avg={};{};{}", avg, threshold(t.warn_erase_avg), threshold(t.crit_erase_avg)));
+    }
+    // The part of the status derived from the remaining life is left to the
+    // life thresholds.
+    let life_status = report.remaining_life_percent.map(HealthStatus::from_remaining_life).unwrap_or_default();
+    if life_status.worst(report.status) != life_status {
+        let status_state = match report.status {
+            HealthStatus::Critical => CheckState::Critical,
+            HealthStatus::Warning => CheckState::Warning,
+            HealthStatus::Good | HealthStatus::Unknown => CheckState::Ok,
+        };
+        check("health status", report.status.name().to_string(), status_state, true);
+    }
+    if let Some(power_cycles) = report.power_cycles {
+        perfdata.push(format!("power_cycles={}", power_cycles));
+    }
+
+    if !checked {
+        return CheckResult {
+            state: CheckState::Unknown,
+            summary: format!("{} reports no value to check", report.card_type),
+            perfdata,
+        };
+    }
+
+    let summary = match (problems.is_empty(), report.remaining_life_percent) {
+        (false, _) => format!("{}: {}", report.card_type, problems.join(", ")),
+        (true, Some(life)) => format!("{}: life {}%", report.card_type, life),
+        (true, None) => format!("{}: healthy", report.card_type),
+    };
+    return CheckResult { state, summary, perfdata };
+}
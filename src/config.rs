@@ -0,0 +1,110 @@
+//! sdmon configuration file (TOML).
+//!
+//! ```toml
+//! [thresholds]
+//! warn-life = 20
+//! crit-life = 10
+//!
+//! [vendor.Sandisk]
+//! warn-grown-bad = 5
+//!
+//! [device."/dev/mmcblk1"]
+//! crit-life = 15
+//!
+//! [[probe]]
+//! mid = 0x74
+//! never = [0x53420001]
+//!
+//! [daemon]
+//! devices = ["/dev/mmcblk0", "/dev/mmcblk1"]
+//! interval = "6h"
+//! history = "/var/lib/sdmon/history.jsonl"
+//! ```
+//!
+//! Device sections are keyed by device path or CID serial number, vendor
+//! sections by the vendor name of the report (case insensitive). `[[probe]]`
+//! rules are described in [`policy`](crate::policy).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::check::Thresholds;
+use crate::error::SdmonError;
+use crate::policy::ProbeRule;
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub thresholds: Thresholds,
+    pub vendor: BTreeMap<String, Thresholds>,
+    pub device: BTreeMap<String, Thresholds>,
+    pub daemon: DaemonConfig,
+    pub probe: Vec<ProbeRule>,
+}
+
+/// Settings of `sdmon daemon`, overridden by its command line options.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Devices to poll, all discovered cards when empty.
+    pub devices: Vec<String>,
+    /// Poll interval, see [`parse_duration`].
+    pub interval: Option<String>,
+    pub history: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, SdmonError> {
+        let config: Config = toml::from_str(text).map_err(|err| SdmonError::Parse(format!("invalid configuration: {}", err.message())))?;
+        for (index, rule) in config.probe.iter().enumerate() {
+            rule.validate().map_err(|message| SdmonError::Parse(format!("invalid configuration: probe rule {}: {}", index + 1, message)))?;
+        }
+        return Ok(config);
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SdmonError> {
+        let text = fs::read_to_string(path).map_err(|err| SdmonError::io(path, err))?;
+        return Config::parse(&text);
+    }
+
+    /// Thresholds of `device`: the built-in defaults overridden by the
+    /// `[thresholds]` section, the vendor section and the device section, in
+    /// this order.
+    pub fn thresholds(&self, device: &str, serial: Option<&str>, vendor: &str) -> Thresholds {
+        let mut thresholds = Thresholds::defaults().merge(&self.thresholds);
+
+        for (name, vendor_thresholds) in &self.vendor {
+            if name.eq_ignore_ascii_case(vendor) {
+                thresholds = thresholds.merge(vendor_thresholds);
+            }
+        }
+        for key in [Some(device), serial].into_iter().flatten() {
+            if let Some(device_thresholds) = self.device.get(key) {
+                thresholds = thresholds.merge(device_thresholds);
+            }
+        }
+        return thresholds;
+    }
+}
+
+/// Parses durations like `90`, `45s`, `30m`, `6h` or `1d`; plain numbers are
+/// seconds.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    let seconds: u64 = number.parse().ok()?;
+    return Some(Duration::from_secs(seconds.checked_mul(multiplier)?)).filter(|duration| !duration.is_zero());
+}
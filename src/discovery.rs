@@ -0,0 +1,136 @@
+//! Enumeration of the MMC/SD cards of the host through sysfs.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Card type as reported by the sysfs `type` attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardKind {
+    Sd,
+    /// eMMC or MMC.
+    Mmc,
+    Sdio,
+    /// SD combo cards with an SDIO function.
+    SdCombo,
+    Unknown,
+}
+
+impl CardKind {
+    pub fn from_sysfs(value: &str) -> Self {
+        match value.trim() {
+            "SD" => CardKind::Sd,
+            "MMC" => CardKind::Mmc,
+            "SDIO" => CardKind::Sdio,
+            "SDcombo" => CardKind::SdCombo,
+            _ => CardKind::Unknown,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CardKind::Sd => "SD",
+            CardKind::Mmc => "MMC",
+            CardKind::Sdio => "SDIO",
+            CardKind::SdCombo => "SDcombo",
+            CardKind::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the card has a block device CMD56 can be sent to.
+    pub fn has_storage(&self) -> bool {
+        return !matches!(self, CardKind::Sdio);
+    }
+}
+
+/// A card found in sysfs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MmcCard {
+    pub kind: CardKind,
+    /// Card directory, e.g. `/sys/class/mmc_host/mmc0/mmc0:aaaa`.
+    pub sysfs_path: PathBuf,
+    /// Whole-card block device name, e.g. `mmcblk0`; `None` for SDIO cards.
+    pub block_name: Option<String>,
+}
+
+impl MmcCard {
+    /// Device node of the card, e.g. `/dev/mmcblk0`.
+    pub fn device(&self) -> Option<String> {
+        return self.block_name.as_ref().map(|name| format!("/dev/{}", name));
+    }
+}
+
+/// `mmcblk<N>` without partition (`p1`), boot area (`boot0`) or RPMB suffix.
+pub fn is_whole_card_block(name: &str) -> bool {
+    return name.strip_prefix("mmcblk")
+        .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()));
+}
+
+/// Card type from the `type` attribute in `card_dir`.
+pub fn read_kind(card_dir: &Path) -> CardKind {
+    return fs::read_to_string(card_dir.join("type"))
+        .map(|value| CardKind::from_sysfs(&value))
+        .unwrap_or(CardKind::Unknown);
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    return entries;
+}
+
+fn file_name(path: &Path) -> String {
+    return path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+}
+
+/// Lists the cards below `sysfs_root` (normally `/sys`).
+///
+/// Cards are taken from `class/mmc_host/*/mmc*/`, block devices from
+/// `block/mmcblk*` are added when their card was not found through a host.
+pub fn discover_in(sysfs_root: &Path) -> Vec<MmcCard> {
+    // Keyed by block device name, or card directory for cards without one.
+    let mut cards: BTreeMap<String, MmcCard> = BTreeMap::new();
+
+    for host in sorted_entries(&sysfs_root.join("class/mmc_host")) {
+        for card_dir in sorted_entries(&host) {
+            let host_name = file_name(&host);
+            if !file_name(&card_dir).starts_with(&format!("{}:", host_name)) {
+                continue;
+            }
+
+            let block_name = sorted_entries(&card_dir.join("block")).iter()
+                .map(|path| file_name(path))
+                .find(|name| is_whole_card_block(name));
+            let key = block_name.clone().unwrap_or_else(|| card_dir.display().to_string());
+
+            cards.insert(key, MmcCard { kind: read_kind(&card_dir), sysfs_path: card_dir, block_name });
+        }
+    }
+
+    for block in sorted_entries(&sysfs_root.join("block")) {
+        let name = file_name(&block);
+        if !is_whole_card_block(&name) || cards.contains_key(&name) {
+            continue;
+        }
+
+        let card_dir = block.join("device");
+        cards.insert(name.clone(), MmcCard { kind: read_kind(&card_dir), sysfs_path: card_dir, block_name: Some(name) });
+    }
+
+    return cards.into_values().collect();
+}
+
+/// sysfs directory of the card behind a device node, e.g.
+/// `/sys/block/mmcblk0/device` for `/dev/mmcblk0`.
+pub fn card_dir(device: &str) -> PathBuf {
+    let path = fs::canonicalize(device).unwrap_or_else(|_| PathBuf::from(device));
+    return Path::new("/sys/block").join(file_name(&path)).join("device");
+}
+
+/// Lists the cards of this host.
+pub fn discover() -> Vec<MmcCard> {
+    return discover_in(Path::new("/sys"));
+}
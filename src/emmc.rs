@@ -0,0 +1,287 @@
+//! eMMC health from the standard EXT_CSD register (JESD84-B51) and the
+//! vendor specific health reports.
+//!
+//! eMMC devices rarely answer the SD vendor CMD56 commands, but since eMMC 5.0
+//! EXT_CSD carries life time estimates and the pre-EOL state. Erase counts and
+//! bad block tables are only available through vendor commands.
+
+use crate::error::SdmonError;
+use crate::mmc_ioc_cmd::{cmd56_data_in, debug_dump, send_batch, with_retries, Cmd56, SDB1, SDBlock, COMMAND_FLAGS_AC_R1B, COMMAND_FLAGS_DATA_IN, SD_BLOCK_SIZE};
+use crate::parse_vendor_block;
+use crate::report::{FieldValue, HealthReport, HealthStatus};
+use crate::transport::{CommandOptions, MmcCommand, MmcTransport};
+
+pub const MMC_SEND_EXT_CSD: u32 = 8;
+
+const EXT_CSD_CACHE_SIZE: usize = 249;
+const EXT_CSD_SEC_COUNT: usize = 212;
+const EXT_CSD_REV: usize = 192;
+const EXT_CSD_FIRMWARE_VERSION: usize = 254;
+const EXT_CSD_DEVICE_VERSION: usize = 262;
+const EXT_CSD_PRE_EOL_INFO: usize = 267;
+const EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_A: usize = 268;
+const EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_B: usize = 269;
+const EXT_CSD_VENDOR_PROPRIETARY_HEALTH_REPORT: usize = 301;
+
+/// Reads the 512 byte EXT_CSD register with CMD8 (SEND_EXT_CSD).
+pub fn read_ext_csd(transport: &mut dyn MmcTransport, options: &CommandOptions, debug: bool) -> Result<SDB1, SdmonError> {
+    let command = MmcCommand::read(MMC_SEND_EXT_CSD, 0, COMMAND_FLAGS_DATA_IN, SD_BLOCK_SIZE as u32).with_options(*options);
+
+    let res = with_retries(options, debug, || {
+        let res = transport.send(&command);
+        if debug {
+            debug_dump(&command, &res);
+        }
+        res
+    });
+
+    return res.map(|response| SDB1::from_slice(&response.data));
+}
+
+fn le32(block: &SDBlock, offset: usize) -> u32 {
+    return u32::from_le_bytes([block[offset], block[offset + 1], block[offset + 2], block[offset + 3]]);
+}
+
+fn pre_eol_info(value: u8) -> String {
+    match value {
+        0x00 => "Not defined".to_string(),
+        0x01 => "Normal".to_string(),
+        0x02 => "Warning".to_string(),
+        0x03 => "Urgent".to_string(),
+        _ => format!("Reserved ({})", value),
+    }
+}
+
+fn pre_eol_status(value: u8) -> HealthStatus {
+    match value {
+        0x01 => HealthStatus::Good,
+        0x02 => HealthStatus::Warning,
+        0x03 => HealthStatus::Critical,
+        _ => HealthStatus::Unknown,
+    }
+}
+
+fn life_time_estimation(value: u8) -> String {
+    match value {
+        0x00 => "Not defined".to_string(),
+        0x01..=0x0A => format!("{}% - {}% used", (value - 1) * 10, value * 10),
+        0x0B => "Exceeded its maximum estimated life time".to_string(),
+        _ => format!("Reserved ({})", value),
+    }
+}
+
+/// Remaining life for a DEVICE_LIFE_TIME_EST value, taking the upper bound
+/// of the used range (0x01 "0% - 10% used" is 90% remaining).
+pub fn life_time_remaining_percent(value: u8) -> Option<f32> {
+    match value {
+        0x01..=0x0A => Some(100.0 - 10.0 * f32::from(value)),
+        0x0B => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Decodes the health related EXT_CSD fields. The normalized remaining life
+/// is the worse of the type A (SLC) and type B (MLC) estimates.
+pub fn parse_ext_csd(vendor: &str, ext_csd: &SDBlock) -> HealthReport {
+    let mut report = HealthReport::new(vendor, "eMMC");
+    let life_a = ext_csd[EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_A];
+    let life_b = ext_csd[EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_B];
+    let firmware = &ext_csd[EXT_CSD_FIRMWARE_VERSION..EXT_CSD_FIRMWARE_VERSION + 8];
+
+    report.add("EXT_CSD revision", ext_csd[EXT_CSD_REV]);
+    report.add_with_unit("Capacity", u64::from(le32(ext_csd, EXT_CSD_SEC_COUNT)) * 512, "bytes");
+    report.add("Firmware version", FieldValue::Bytes(firmware.to_vec()));
+    report.add("Device version", FieldValue::Bytes(ext_csd[EXT_CSD_DEVICE_VERSION..EXT_CSD_DEVICE_VERSION + 2].to_vec()));
+    report.add_with_unit("Cache size", le32(ext_csd, EXT_CSD_CACHE_SIZE), "KiB");
+    report.add("Pre EOL info", pre_eol_info(ext_csd[EXT_CSD_PRE_EOL_INFO]));
+    report.add("Life time estimation A (SLC)", life_time_estimation(life_a));
+    report.add("Life time estimation B (MLC)", life_time_estimation(life_b));
+    report.add("Vendor proprietary health report", FieldValue::Bytes(
+        ext_csd[EXT_CSD_VENDOR_PROPRIETARY_HEALTH_REPORT..EXT_CSD_VENDOR_PROPRIETARY_HEALTH_REPORT + 32].to_vec()));
+
+    let remaining = match (life_time_remaining_percent(life_a), life_time_remaining_percent(life_b)) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    if let Some(remaining) = remaining {
+        report.set_remaining_life(remaining);
+    }
+    report.status = report.status.worst(pre_eol_status(ext_csd[EXT_CSD_PRE_EOL_INFO]));
+    return report;
+}
+
+const MMC_READ_SINGLE_BLOCK: u32 = 17;
+const MMC_VENDOR_CMD62: u32 = 62;
+
+const MICRON_HEALTH_REPORT: u32 = 0x11;
+const SAMSUNG_VENDOR_MODE_KEY: u32 = 0xEFAC62EC;
+const SAMSUNG_SMART_REPORT_ENABLE: u32 = 0x0000CCEE;
+const SAMSUNG_SMART_REPORT_DISABLE: u32 = 0x00DECCEE;
+const SAMSUNG_SMART_REPORT_ADDRESS: u32 = 0x1000;
+
+/// Vendor health command sets of eMMC devices.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EmmcVendorCommand {
+    /// CMD56 with argument 0x11, Micron device health report.
+    Micron,
+    /// CMD62 vendor mode, smart report read with CMD17, vendor mode left again.
+    Samsung,
+    /// CMD56 with the Phison argument of Micron SD cards, used by Kingston.
+    Phison,
+    /// CMD56 with argument 1 like SanDisk SD cards, used by iNAND.
+    Sandisk,
+}
+
+pub const EMMC_VENDOR_COMMANDS: [EmmcVendorCommand; 4] = [
+    EmmcVendorCommand::Micron, EmmcVendorCommand::Samsung, EmmcVendorCommand::Phison, EmmcVendorCommand::Sandisk,
+];
+
+fn send_no_data(transport: &mut dyn MmcTransport, opcode: u32, arg: u32, options: &CommandOptions, debug: bool) -> Result<(), SdmonError> {
+    let command = MmcCommand::no_data(opcode, arg, COMMAND_FLAGS_AC_R1B).with_options(*options);
+
+    let res = transport.send(&command);
+    if debug {
+        debug_dump(&command, &res);
+    }
+
+    return res.map(|_| ());
+}
+
+/// Timings of `options` apply, but a failed sequence is not repeated; the
+/// vendor mode is left first.
+fn read_samsung_smart_report(transport: &mut dyn MmcTransport, options: &CommandOptions, debug: bool) -> Result<SDB1, SdmonError> {
+    // Entering the vendor mode and reading the report is one batch, a
+    // filesystem CMD17 in between would read the report as user data.
+    let commands = [
+        MmcCommand::no_data(MMC_VENDOR_CMD62, SAMSUNG_VENDOR_MODE_KEY, COMMAND_FLAGS_AC_R1B).with_options(*options),
+        MmcCommand::no_data(MMC_VENDOR_CMD62, SAMSUNG_SMART_REPORT_ENABLE, COMMAND_FLAGS_AC_R1B).with_options(*options),
+        MmcCommand::read(MMC_READ_SINGLE_BLOCK, SAMSUNG_SMART_REPORT_ADDRESS, COMMAND_FLAGS_DATA_IN, SD_BLOCK_SIZE as u32).with_options(*options),
+    ];
+    let res = send_batch(transport, &commands, debug);
+    if let Err(err @ SdmonError::WriteRefused { .. }) = res {
+        // Read-only mode, the card never entered the vendor mode.
+        return Err(err);
+    }
+
+    // Leave the vendor mode even when the batch failed, otherwise CMD17
+    // keeps returning the report instead of user data.
+    let disabled = send_no_data(transport, MMC_VENDOR_CMD62, SAMSUNG_VENDOR_MODE_KEY, options, debug)
+        .and_then(|_| send_no_data(transport, MMC_VENDOR_CMD62, SAMSUNG_SMART_REPORT_DISABLE, options, debug));
+
+    let block = res.map(|responses| SDB1::from_slice(&responses[2].data))?;
+    disabled?;
+    return Ok(block);
+}
+
+/// Reads the vendor health report of an eMMC device.
+pub fn read_vendor_report(transport: &mut dyn MmcTransport, command: EmmcVendorCommand, options: &CommandOptions, debug: bool) -> Result<SDB1, SdmonError> {
+    match command {
+        EmmcVendorCommand::Micron => cmd56_data_in(transport, MICRON_HEALTH_REPORT, options, debug).map(|(block, _)| block),
+        EmmcVendorCommand::Samsung => read_samsung_smart_report(transport, options, debug),
+        EmmcVendorCommand::Phison => cmd56_data_in(transport, Cmd56::Micron as u32, options, debug).map(|(block, _)| block),
+        EmmcVendorCommand::Sandisk => cmd56_data_in(transport, Cmd56::Sandisk as u32, options, debug).map(|(block, _)| block),
+    }
+}
+
+fn be32(block: &SDBlock, offset: usize) -> u32 {
+    return u32::from_be_bytes([block[offset], block[offset + 1], block[offset + 2], block[offset + 3]]);
+}
+
+fn parse_micron(block: &SDBlock) -> Option<HealthReport> {
+    if block[..40].iter().all(|byte| *byte == 0) {
+        return None;
+    }
+
+    let mut report = HealthReport::new("Micron", "Micron eMMC");
+    let initial_bad = be32(block, 0);
+    let runtime_bad = be32(block, 4);
+    let spare = be32(block, 8);
+
+    report.add("Initial bad blocks", initial_bad);
+    report.add("Runtime bad blocks", runtime_bad);
+    report.add("Remaining spare blocks", spare);
+    report.add_with_unit("MLC minimum erase count", be32(block, 16), "cycles");
+    report.add_with_unit("MLC maximum erase count", be32(block, 20), "cycles");
+    report.add_with_unit("MLC average erase count", be32(block, 24), "cycles");
+    report.add_with_unit("SLC minimum erase count", be32(block, 28), "cycles");
+    report.add_with_unit("SLC maximum erase count", be32(block, 32), "cycles");
+    report.add_with_unit("SLC average erase count", be32(block, 36), "cycles");
+
+    report.spare_blocks = Some(spare.into());
+    report.bad_blocks.factory = Some(initial_bad.into());
+    report.bad_blocks.grown = Some(runtime_bad.into());
+    report.erase_count.min = Some(be32(block, 16).into());
+    report.erase_count.max = Some(be32(block, 20).into());
+    report.erase_count.avg = Some(be32(block, 24).into());
+    return Some(report);
+}
+
+const SAMSUNG_MAX_BANKS: usize = 4;
+
+fn parse_samsung(block: &SDBlock) -> Option<HealthReport> {
+    let banks = le32(block, 16) as usize;
+    if !(1..=SAMSUNG_MAX_BANKS).contains(&banks) {
+        return None;
+    }
+
+    let mut report = HealthReport::new("Samsung", "Samsung eMMC");
+    report.add("Error mode", le32(block, 0));
+    report.add("Super block size", le32(block, 4));
+    report.add("Super page size", le32(block, 8));
+    report.add("Optimal write size", le32(block, 12));
+    report.add("Number of banks", le32(block, 16));
+
+    let (mut initial_bad, mut runtime_bad, mut reserved) = (0u64, 0u64, 0u64);
+    for bank in 0..banks {
+        let offset = 20 + bank * 12;
+        report.add(&format!("Bank {} initial bad blocks", bank), le32(block, offset));
+        report.add(&format!("Bank {} runtime bad blocks", bank), le32(block, offset + 4));
+        report.add(&format!("Bank {} reserved blocks", bank), le32(block, offset + 8));
+        initial_bad += u64::from(le32(block, offset));
+        runtime_bad += u64::from(le32(block, offset + 4));
+        reserved += u64::from(le32(block, offset + 8));
+    }
+
+    report.add_with_unit("Maximum erase count", le32(block, 68), "cycles");
+    report.add_with_unit("Minimum erase count", le32(block, 72), "cycles");
+    report.add_with_unit("Average erase count", le32(block, 76), "cycles");
+    report.add("Read reclaim count", le32(block, 80));
+    report.add("Optimal trim size", le32(block, 84));
+
+    report.spare_blocks = Some(reserved);
+    report.bad_blocks.factory = Some(initial_bad);
+    report.bad_blocks.grown = Some(runtime_bad);
+    report.erase_count.max = Some(le32(block, 68).into());
+    report.erase_count.min = Some(le32(block, 72).into());
+    report.erase_count.avg = Some(le32(block, 76).into());
+    return Some(report);
+}
+
+/// Decodes a vendor health report read with `command`, `None` when the block
+/// does not look like one.
+pub fn parse_vendor_report(command: EmmcVendorCommand, block: &SDB1) -> Option<HealthReport> {
+    match command {
+        EmmcVendorCommand::Micron => parse_micron(block.data()),
+        EmmcVendorCommand::Samsung => parse_samsung(block.data()),
+        EmmcVendorCommand::Phison => parse_vendor_block(Cmd56::Micron, block),
+        EmmcVendorCommand::Sandisk => parse_vendor_block(Cmd56::Sandisk, block),
+    }
+}
+
+/// Adds the fields and normalized values of a vendor report to the EXT_CSD
+/// report. The EXT_CSD life time estimate wins over the vendor's, the status
+/// is the worse of both.
+pub fn merge_vendor_report(report: &mut HealthReport, vendor: HealthReport) {
+    report.card_type = vendor.card_type;
+    report.fields.extend(vendor.fields);
+    report.remaining_life_percent = report.remaining_life_percent.or(vendor.remaining_life_percent);
+    report.status = report.status.worst(vendor.status);
+    report.power_cycles = vendor.power_cycles.or(report.power_cycles);
+    report.spare_blocks = vendor.spare_blocks.or(report.spare_blocks);
+    report.bad_blocks.factory = vendor.bad_blocks.factory.or(report.bad_blocks.factory);
+    report.bad_blocks.grown = vendor.bad_blocks.grown.or(report.bad_blocks.grown);
+    report.erase_count.min = vendor.erase_count.min.or(report.erase_count.min);
+    report.erase_count.max = vendor.erase_count.max.or(report.erase_count.max);
+    report.erase_count.avg = vendor.erase_count.avg.or(report.erase_count.avg);
+    report.erase_count.total = vendor.erase_count.total.or(report.erase_count.total);
+}
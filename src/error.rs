@@ -0,0 +1,106 @@
+use nix::errno::Errno;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::path::Path;
+
+use crate::mmc_ioc_cmd::SDBlock;
+use crate::status::CardStatus;
+use crate::transport::MmcCommand;
+
+/// Errors reported by the sdmon library.
+///
+/// The CLI maps every variant to its own exit code, see [`SdmonError::exit_code`].
+#[derive(Debug)]
+pub enum SdmonError {
+    /// The device node does not exist.
+    DeviceNotFound { device: String },
+    /// Opening the device or issuing MMC_IOC_CMD was not permitted
+    /// (the ioctl requires CAP_SYS_RAWIO).
+    PermissionDenied { device: String },
+    /// Any other failure opening the device.
+    Open { device: String, source: io::Error },
+    /// An MMC_IOC_CMD ioctl failed.
+    Ioctl { opcode: u32, arg: u32, errno: Errno },
+    /// None of the CMD56 commands was accepted by the card.
+    Unsupported,
+    /// The card answered CMD56 but no parser recognized the block; `refused`
+    /// are the commands skipped in read-only mode.
+    UnknownSignature { cmd56_arg: u32, block: Box<SDBlock>, refused: Vec<MmcCommand> },
+    /// Malformed input such as a block dump file.
+    Parse(String),
+    /// Reading or writing a file other than the device failed.
+    Io { path: String, source: io::Error },
+    /// The command sends data to the card or switches its mode and was not
+    /// issued in read-only mode.
+    WriteRefused { opcode: u32, arg: u32 },
+    /// The card answered with ILLEGAL_COMMAND in its R1 status, i.e. it does
+    /// not support the command or argument.
+    IllegalCommand { opcode: u32, arg: u32, status: CardStatus },
+}
+
+impl SdmonError {
+    pub fn open(device: &str, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::NotFound => SdmonError::DeviceNotFound { device: device.to_string() },
+            io::ErrorKind::PermissionDenied => SdmonError::PermissionDenied { device: device.to_string() },
+            _ => SdmonError::Open { device: device.to_string(), source },
+        }
+    }
+
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        return SdmonError::Io { path: path.display().to_string(), source };
+    }
+
+    /// CLI exit code of the error. 0 is success and 1 is reserved for usage
+    /// and output errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SdmonError::DeviceNotFound { .. } => 2,
+            SdmonError::PermissionDenied { .. } => 3,
+            SdmonError::Open { .. } => 4,
+            SdmonError::Ioctl { .. } => 5,
+            SdmonError::Unsupported => 6,
+            SdmonError::UnknownSignature { .. } => 7,
+            SdmonError::Parse(_) => 8,
+            SdmonError::Io { .. } => 9,
+            SdmonError::WriteRefused { .. } => 10,
+            SdmonError::IllegalCommand { .. } => 11,
+        }
+    }
+}
+
+impl Display for SdmonError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SdmonError::DeviceNotFound { device } => write!(f, "device {} not found", device),
+            SdmonError::PermissionDenied { device } => write!(f, "permission denied for {}", device),
+            SdmonError::Open { device, source } => write!(f, "opening {} failed: {}", device, source),
+            SdmonError::Ioctl { opcode, arg, errno } => write!(f, "CMD{} arg 0x{:08X} failed: {}", opcode, arg, errno),
+            SdmonError::Unsupported => write!(f, "card does not support CMD56"),
+            SdmonError::UnknownSignature { cmd56_arg, block, refused } => {
+                write!(f, "CMD56 arg 0x{:08X} returned an unknown signature {:02X?} {:02X?}", cmd56_arg, block[0], block[1])?;
+                for command in refused {
+                    write!(f, ", CMD{} arg 0x{:08X} skipped in read-only mode", command.opcode, command.arg)?;
+                }
+                return Ok(());
+            }
+            SdmonError::Parse(message) => write!(f, "parse error: {}", message),
+            SdmonError::Io { path, source } => write!(f, "{}: {}", path, source),
+            SdmonError::WriteRefused { opcode, arg } =>
+                write!(f, "CMD{} arg 0x{:08X} skipped, it changes the card and read-only mode is set", opcode, arg),
+            SdmonError::IllegalCommand { opcode, arg, status } =>
+                write!(f, "CMD{} arg 0x{:08X} not supported by the card, status {}", opcode, arg, status),
+        }
+    }
+}
+
+impl std::error::Error for SdmonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SdmonError::Open { source, .. } | SdmonError::Io { source, .. } => Some(source),
+            SdmonError::Ioctl { errno, .. } => Some(errno),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Append-only health history.
+//!
+//! Every poll of `sdmon daemon` appends one JSON line per device to the
+//! history file: the time, the device, the CID serial number, the complete
+//! [`HealthReport`] and the raw block. Lines are never rewritten, so the file
+//! can be rotated or copied while the daemon runs.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SdmonError;
+use crate::report::{hex, HealthReport};
+use crate::Detection;
+
+/// Default location of the history file.
+pub const DEFAULT_HISTORY_PATH: &str = "/var/lib/sdmon/history.jsonl";
+
+/// One sample of one device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub device: String,
+    /// CID serial number, e.g. `"0x1234abcd"`.
+    pub serial: Option<String>,
+    #[serde(flatten)]
+    pub report: HealthReport,
+    #[serde(with = "hex")]
+    pub raw: Vec<u8>,
+}
+
+impl HistoryRecord {
+    pub fn new(timestamp: u64, device: &str, detection: &Detection) -> Self {
+        return HistoryRecord {
+            timestamp,
+            device: device.to_string(),
+            serial: detection.registers.serial(),
+            report: detection.report.clone(),
+            raw: detection.block.data().to_vec(),
+        };
+    }
+
+    /// Whether the record belongs to the card with `serial`, compared case
+    /// insensitively with or without `0x` prefix.
+    pub fn has_serial(&self, serial: &str) -> bool {
+        let normalize = |serial: &str| serial.trim_start_matches("0x").trim_start_matches("0X").to_ascii_lowercase();
+        return self.serial.as_deref().is_some_and(|own| normalize(own) == normalize(serial));
+    }
+}
+
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(path: &Path) -> Self {
+        return HistoryStore { path: path.to_path_buf() };
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    /// Appends `record` as one line, creating the file and its directory.
+    pub fn append(&self, record: &HistoryRecord) -> Result<(), SdmonError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|err| SdmonError::io(dir, err))?;
+        }
+
+        let mut line = serde_json::to_string(record).expect("history record is always serializable");
+        line.push('\n');
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)
+            .map_err(|err| SdmonError::io(&self.path, err))?;
+        return file.write_all(line.as_bytes()).map_err(|err| SdmonError::io(&self.path, err));
+    }
+
+    /// Reads all records in file order, an empty history when the file does
+    /// not exist yet.
+    pub fn records(&self) -> Result<Vec<HistoryRecord>, SdmonError> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(SdmonError::io(&self.path, err)),
+        };
+
+        let mut records = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(line).map_err(|err| {
+                SdmonError::Parse(format!("{} line {}: {}", self.path.display(), number + 1, err))
+            })?;
+            records.push(record);
+        }
+        return Ok(records);
+    }
+
+    /// Records of the card with `serial`, oldest first.
+    pub fn series(&self, serial: &str) -> Result<Vec<HistoryRecord>, SdmonError> {
+        let mut records: Vec<HistoryRecord> = self.records()?.into_iter().filter(|record| record.has_serial(serial)).collect();
+        records.sort_by_key(|record| record.timestamp);
+        return Ok(records);
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+}
+
+/// Formats a Unix timestamp as UTC `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+
+    // Civil date from days since 1970-01-01, proleptic Gregorian calendar.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    return format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60);
+}
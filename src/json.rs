@@ -0,0 +1,56 @@
+use serde::Serialize;
+
+use crate::registers::CardRegisters;
+use crate::report::{hex, HealthReport};
+use crate::status::CardStatus;
+use crate::Detection;
+
+/// JSON document emitted by `sdmon --json` for one device.
+#[derive(Serialize)]
+pub struct DeviceDocument<'a> {
+    pub device: &'a str,
+    /// CID serial number, e.g. `"0x1234abcd"`.
+    pub serial: Option<String>,
+    /// Opcode of the command that produced the block, 56 or 8 (EXT_CSD).
+    pub opcode: u32,
+    /// CMD56 argument that produced the block, e.g. `"0x110005FB"`.
+    pub cmd56_arg: String,
+    pub registers: &'a CardRegisters,
+    #[serde(flatten)]
+    pub report: &'a HealthReport,
+    /// The 512 byte CMD56 block or EXT_CSD as uppercase hex.
+    pub raw: String,
+    /// The vendor health report of an eMMC device as uppercase hex.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor_raw: Option<String>,
+    /// R1 card status of the CMD56 read; not `status`, which is the health
+    /// status of the flattened report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card_status: Option<CardStatus>,
+    /// Commands skipped in read-only mode, e.g. `"CMD56 arg 0x00000010"`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub refused: Vec<String>,
+}
+
+impl<'a> DeviceDocument<'a> {
+    pub fn new(device: &'a str, detection: &'a Detection) -> Self {
+        return DeviceDocument {
+            device,
+            serial: detection.registers.serial(),
+            opcode: detection.opcode,
+            cmd56_arg: format!("0x{:08X}", detection.cmd56_arg),
+            registers: &detection.registers,
+            report: &detection.report,
+            raw: hex::encode(detection.block.data()),
+            vendor_raw: detection.vendor_block.as_ref().map(|block| hex::encode(block.data())),
+            card_status: detection.status,
+            refused: detection.refused.iter().map(|command| format!("CMD{} arg 0x{:08X}", command.opcode, command.arg)).collect(),
+        };
+    }
+}
+
+/// Renders the document of one device as a single line of JSON.
+pub fn to_json(device: &str, detection: &Detection) -> String {
+    return serde_json::to_string(&DeviceDocument::new(device, detection))
+        .expect("health report is always serializable");
+}
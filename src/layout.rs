@@ -0,0 +1,202 @@
+//! Declarative layouts of vendor health blocks.
+//!
+//! A [`Layout`] lists the fields of a block in output order; [`Layout::parse`]
+//! decodes them into a [`HealthReport`] and fills the normalized members from
+//! the fields marked with a [`Role`]. Adding a vendor or fixing an offset is a
+//! change to a table in [`parsers`](crate::parsers).
+
+use crate::mmc_ioc_cmd::SDBlock;
+use crate::report::{FieldValue, HealthReport};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+/// Bytes a field is read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// `len` bytes starting at `offset`.
+    Range(usize, usize),
+    /// Bytes at these offsets, in significance order for numbers.
+    Scattered(&'static [usize]),
+}
+
+/// Names of the values of an enumerated field.
+#[derive(Debug, PartialEq)]
+pub struct EnumMap {
+    pub values: &'static [(u64, &'static str)],
+    /// Prefix for unlisted values, rendered as `<prefix> (<value>)`.
+    pub unknown: &'static str,
+}
+
+impl EnumMap {
+    pub fn name(&self, value: u64) -> String {
+        match self.values.iter().find(|(key, _)| *key == value) {
+            Some((_, name)) => name.to_string(),
+            None => format!("{} ({})", self.unknown, value),
+        }
+    }
+}
+
+/// How the bytes of a field are decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Unsigned integer.
+    Uint(Endian),
+    /// Unsigned integer divided by the divisor, e.g. 1024 for GB to TB.
+    Scaled(Endian, f64),
+    /// Sum of all bytes.
+    Sum,
+    /// Hex bytes.
+    Bytes,
+    /// Bytes as decimal numbers.
+    List,
+    /// Every byte as one character.
+    Ascii,
+    /// UTF-8 with invalid sequences replaced.
+    Utf8,
+    /// ASCII with control and non-ASCII bytes replaced by `_`.
+    PrintableAscii,
+    /// Big endian unsigned integer named by the map.
+    Enum(&'static EnumMap),
+}
+
+/// Normalized [`HealthReport`] member a field fills.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    RemainingLife,
+    /// Percent of the life used, remaining life is 100 minus the value.
+    LifeUsed,
+    PowerCycles,
+    SpareBlocks,
+    FactoryBadBlocks,
+    GrownBadBlocks,
+    EraseMin,
+    EraseMax,
+    EraseAvg,
+    EraseTotal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub source: Source,
+    pub format: Format,
+    pub unit: Option<&'static str>,
+    pub role: Option<Role>,
+}
+
+impl FieldSpec {
+    pub const fn new(name: &'static str, source: Source, format: Format) -> Self {
+        return FieldSpec { name, source, format, unit: None, role: None };
+    }
+
+    /// Single byte unsigned integer.
+    pub const fn u8(name: &'static str, offset: usize) -> Self {
+        return FieldSpec::new(name, Source::Range(offset, 1), Format::Uint(Endian::Big));
+    }
+
+    pub const fn be(name: &'static str, offset: usize, len: usize) -> Self {
+        return FieldSpec::new(name, Source::Range(offset, len), Format::Uint(Endian::Big));
+    }
+
+    pub const fn le(name: &'static str, offset: usize, len: usize) -> Self {
+        return FieldSpec::new(name, Source::Range(offset, len), Format::Uint(Endian::Little));
+    }
+
+    pub const fn bytes(name: &'static str, offset: usize, len: usize, format: Format) -> Self {
+        return FieldSpec::new(name, Source::Range(offset, len), format);
+    }
+
+    pub const fn unit(self, unit: &'static str) -> Self {
+        return FieldSpec { unit: Some(unit), ..self };
+    }
+
+    pub const fn role(self, role: Role) -> Self {
+        return FieldSpec { role: Some(role), ..self };
+    }
+
+    fn read(&self, block: &SDBlock) -> Vec<u8> {
+        match self.source {
+            Source::Range(offset, len) => block[offset..offset + len].to_vec(),
+            Source::Scattered(offsets) => offsets.iter().map(|offset| block[*offset]).collect(),
+        }
+    }
+
+    pub fn decode(&self, block: &SDBlock) -> FieldValue {
+        let bytes = self.read(block);
+        let uint = |endian: Endian| -> u64 {
+            let fold = |value: u64, byte: &u8| (value << 8) | u64::from(*byte);
+            match endian {
+                Endian::Big => bytes.iter().fold(0, fold),
+                Endian::Little => bytes.iter().rev().fold(0, fold),
+            }
+        };
+
+        match self.format {
+            Format::Uint(endian) => FieldValue::Uint(uint(endian)),
+            Format::Scaled(endian, divisor) => FieldValue::Float(uint(endian) as f64 / divisor),
+            Format::Sum => FieldValue::Uint(bytes.iter().map(|byte| u64::from(*byte)).sum()),
+            Format::Bytes => FieldValue::Bytes(bytes),
+            Format::List => FieldValue::List(bytes.iter().map(|byte| u64::from(*byte)).collect()),
+            Format::Ascii => FieldValue::Text(bytes.iter().map(|byte| *byte as char).collect()),
+            Format::Utf8 => FieldValue::Text(String::from_utf8_lossy(&bytes).to_string()),
+            Format::PrintableAscii => FieldValue::Text(bytes.iter()
+                .map(|byte| if *byte < 0x20 || *byte > 0x7F { '_' } else { *byte as char })
+                .collect()),
+            Format::Enum(map) => FieldValue::Text(map.name(uint(Endian::Big))),
+        }
+    }
+}
+
+/// Fields of one vendor block, in output order.
+#[derive(Debug)]
+pub struct Layout {
+    pub vendor: &'static str,
+    pub card_type: &'static str,
+    pub fields: &'static [FieldSpec],
+}
+
+fn numeric(value: &FieldValue) -> Option<f64> {
+    match value {
+        FieldValue::Uint(value) => Some(*value as f64),
+        FieldValue::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+impl Layout {
+    pub fn parse(&self, block: &SDBlock) -> HealthReport {
+        let mut report = HealthReport::new(self.vendor, self.card_type);
+
+        for spec in self.fields {
+            let value = spec.decode(block);
+            if let (Some(role), Some(number)) = (spec.role, numeric(&value)) {
+                apply_role(&mut report, role, number);
+            }
+            match spec.unit {
+                Some(unit) => report.add_with_unit(spec.name, value, unit),
+                None => report.add(spec.name, value),
+            }
+        }
+        return report;
+    }
+}
+
+fn apply_role(report: &mut HealthReport, role: Role, value: f64) {
+    let count = Some(value as u64);
+    match role {
+        Role::RemainingLife => report.set_remaining_life(value as f32),
+        Role::LifeUsed => report.set_remaining_life(100.0 - value as f32),
+        Role::PowerCycles => report.power_cycles = count,
+        Role::SpareBlocks => report.spare_blocks = count,
+        Role::FactoryBadBlocks => report.bad_blocks.factory = count,
+        Role::GrownBadBlocks => report.bad_blocks.grown = count,
+        Role::EraseMin => report.erase_count.min = count,
+        Role::EraseMax => report.erase_count.max = count,
+        Role::EraseAvg => report.erase_count.avg = count,
+        Role::EraseTotal => report.erase_count.total = count,
+    }
+}
@@ -0,0 +1,373 @@
+//! sdmon reads the vendor specific health data of SD cards through the
+//! CMD56 (GEN_CMD) command of the MMC block driver, and the health of eMMC
+//! devices from EXT_CSD.
+//!
+//! The binary is a thin wrapper around [`read_health`]; fleet agents can call
+//! the same entry point, or drive [`mmc_ioc_cmd`] and [`parsers`] directly.
+
+#![allow(clippy::needless_return)]
+
+pub mod check;
+pub mod config;
+pub mod discovery;
+pub mod emmc;
+pub mod error;
+pub mod history;
+pub mod json;
+pub mod layout;
+pub mod manufacturers;
+pub mod mmc_ioc_cmd;
+pub mod parsers;
+pub mod policy;
+pub mod prometheus;
+pub mod registers;
+pub mod report;
+pub mod serve;
+pub mod session;
+pub mod status;
+pub mod transport;
+pub mod trend;
+
+use discovery::{card_dir, read_kind, CardKind};
+use emmc::{merge_vendor_report, parse_ext_csd, parse_vendor_report, read_ext_csd, read_vendor_report, MMC_SEND_EXT_CSD};
+use error::SdmonError;
+use manufacturers::mmc_vendor_command;
+use mmc_ioc_cmd::{cmd56_data_in, cmd56_write_read, Cmd56, IoctlTransport, CMDS56, SDB1, SD_BLOCK_SIZE, SD_GEN_CMD};
+use parsers::{get_parsers, get_smartdata_parser};
+use policy::{resolve_mmc_options, ProbePolicy, ProbeRule};
+use std::fs;
+use std::path::Path;
+use registers::{read_registers, CardRegisters};
+use report::HealthReport;
+use status::CardStatus;
+use nix::errno::Errno;
+use transport::{CommandOptions, MmcCommand, MmcTransport, ReadOnlyTransport};
+
+/// CMD56 argument which switches generic SMART capable cards into SMART mode.
+pub const CMD56_SMART_ENABLE: u32 = 0x00000010;
+/// CMD56 argument which reads the SMART block once SMART mode is enabled.
+pub const CMD56_SMART_READ: u32 = 0x00000021;
+
+/// Outcome of probing a card: the block that was read and its decoded report.
+pub struct Detection {
+    /// Opcode of the command that produced the block, 56 (GEN_CMD) or 8
+    /// (SEND_EXT_CSD) for eMMC devices.
+    pub opcode: u32,
+    /// Vendor command that produced the block, `None` for the generic SMART
+    /// path and EXT_CSD.
+    pub command: Option<Cmd56>,
+    /// Argument of the command that produced the block.
+    pub cmd56_arg: u32,
+    pub block: SDB1,
+    pub report: HealthReport,
+    /// Identification registers, empty when the card was not read live.
+    pub registers: CardRegisters,
+    /// Vendor health report of an eMMC device, read in addition to EXT_CSD.
+    pub vendor_block: Option<SDB1>,
+    /// R1 card status of the CMD56 read, `None` when the block was not read
+    /// live or came from EXT_CSD.
+    pub status: Option<CardStatus>,
+    /// Commands skipped in read-only mode.
+    pub refused: Vec<MmcCommand>,
+}
+
+impl Detection {
+    /// Detection of a CMD56 block.
+    pub fn new(command: Option<Cmd56>, cmd56_arg: u32, block: SDB1, report: HealthReport) -> Self {
+        return Detection { opcode: SD_GEN_CMD, command, cmd56_arg, block, report, registers: CardRegisters::default(), vendor_block: None, status: None, refused: Vec::new() };
+    }
+
+    pub fn with_status(self, status: CardStatus) -> Self {
+        return Detection { status: Some(status), ..self };
+    }
+}
+
+/// Runs `block`, read with the vendor command `cmd`, through the vendor
+/// parsers and returns the report of the first one recognizing it.
+pub fn parse_vendor_block(cmd: Cmd56, block: &SDB1) -> Option<HealthReport> {
+    return get_parsers().into_iter()
+        .find(|parser| parser.check_signature(cmd, block.data()))
+        .map(|parser| parser.parse(block.data()));
+}
+
+/// Decodes a block read earlier, picking the parser like [`probe`] does.
+///
+/// With `cmd56_arg` the block is treated as the answer to that argument,
+/// without it the vendor signatures are tried in sweep order and the generic
+/// SMART parser is used when none matches.
+pub fn decode_block(cmd56_arg: Option<u32>, block: SDB1) -> Result<Detection, SdmonError> {
+    let smart = |block: SDB1| {
+        let report = get_smartdata_parser().parse(block.data());
+        Detection::new(None, CMD56_SMART_READ, block, report)
+    };
+
+    match cmd56_arg {
+        Some(CMD56_SMART_READ) => Ok(smart(block)),
+        Some(arg) => {
+            let cmd = Cmd56::try_from(arg)
+                .map_err(|arg| SdmonError::Parse(format!("unknown CMD56 argument 0x{:08X}", arg)))?;
+            match parse_vendor_block(cmd, &block) {
+                Some(report) => Ok(Detection::new(Some(cmd), arg, block, report)),
+                None => Err(SdmonError::UnknownSignature { cmd56_arg: arg, block: Box::new(*block.data()), refused: Vec::new() }),
+            }
+        }
+        None => {
+            for cmd in CMDS56 {
+                if let Some(report) = parse_vendor_block(cmd, &block) {
+                    return Ok(Detection::new(Some(cmd), cmd as u32, block, report));
+                }
+            }
+            Ok(smart(block))
+        }
+    }
+}
+
+/// Loads a block dump, either 512 raw bytes or the text format printed by
+/// `sdmon -d`.
+pub fn load_block(path: &Path) -> Result<SDB1, SdmonError> {
+    let bytes = fs::read(path).map_err(|err| SdmonError::io(path, err))?;
+
+    if bytes.len() == SD_BLOCK_SIZE {
+        return Ok(SDB1::from_slice(&bytes));
+    }
+    return String::from_utf8_lossy(&bytes).parse();
+}
+
+/// Sweeps all known vendor CMD56 arguments, see [`probe_commands`].
+pub fn probe(transport: &mut dyn MmcTransport, debug: bool) -> Result<Detection, SdmonError> {
+    return probe_commands(transport, &CMDS56, debug);
+}
+
+/// Probes with the commands the CID manufacturer ID suggests, sweeping all of
+/// them only when the manufacturer is unknown.
+pub fn probe_card(transport: &mut dyn MmcTransport, registers: &CardRegisters, debug: bool) -> Result<Detection, SdmonError> {
+    return probe_card_with(transport, registers, &ReadOptions::new(debug));
+}
+
+/// [`probe_card`] with the `[[probe]]` rules of the configuration, see
+/// [`policy`], and the command options of `options` on top.
+pub fn probe_card_with(transport: &mut dyn MmcTransport, registers: &CardRegisters, options: &ReadOptions) -> Result<Detection, SdmonError> {
+    let cid = registers.cid.as_ref();
+    let debug = options.debug;
+    let mut policy = if options.sweep_all {
+        ProbePolicy::resolve_sweep(cid, &options.probe_rules)
+    } else {
+        ProbePolicy::resolve(cid, &options.probe_rules)
+    };
+    policy.options = policy.options.merge(&options.command_options);
+
+    if debug {
+        println!("Manufacturer ID {:02X?}, probing {:?}{}", cid.map(|cid| cid.manufacturer_id), policy.commands,
+            if policy.smart { "" } else { " without generic SMART" });
+    }
+
+    return probe_policy(transport, &policy, debug);
+}
+
+/// Tries the vendor CMD56 arguments in `commands` over `transport` and
+/// falls back to the generic SMART sequence when no vendor signature matches.
+pub fn probe_commands(transport: &mut dyn MmcTransport, commands: &[Cmd56], debug: bool) -> Result<Detection, SdmonError> {
+    return probe_policy(transport, &ProbePolicy::new(commands), debug);
+}
+
+/// Tries the vendor commands of `policy` in order and then, unless the
+/// policy forbids it, the generic SMART sequence.
+///
+/// Fails with [`SdmonError::UnknownSignature`] when a vendor command returned
+/// a block nobody recognized and the SMART sequence failed, with
+/// [`SdmonError::IllegalCommand`] when the card rejected the last command it
+/// was sent that way and with [`SdmonError::Unsupported`] when it rejected
+/// every command otherwise.
+pub fn probe_policy(transport: &mut dyn MmcTransport, policy: &ProbePolicy, debug: bool) -> Result<Detection, SdmonError> {
+    let mut unknown: Option<(u32, SDB1)> = None;
+    let mut illegal: Option<SdmonError> = None;
+
+    for &cmd in &policy.commands {
+        let cmd_value = cmd as u32;
+
+        let res = cmd56_data_in(transport, cmd_value, &policy.options, debug);
+        illegal = match &res {
+            Err(SdmonError::IllegalCommand { opcode, arg, status }) => Some(SdmonError::IllegalCommand { opcode: *opcode, arg: *arg, status: *status }),
+            _ => None,
+        };
+
+        if let Ok((data_in, status)) = res {
+            if let Some(report) = parse_vendor_block(cmd, &data_in) {
+                return Ok(Detection::new(Some(cmd), cmd_value, data_in, report).with_status(status));
+            }
+
+            if debug {
+                println!("Command {:010X?} succeeded but no parser available", cmd_value);
+                println!("{}", data_in);
+            }
+            unknown = Some((cmd_value, data_in));
+        }
+        else if let (true, Err(err)) = (debug, &res) {
+            println!("Command {:010X?} failed: {}", cmd_value, err);
+        }
+    }
+
+    if !policy.smart {
+        return Err(match unknown {
+            Some((cmd56_arg, block)) => SdmonError::UnknownSignature { cmd56_arg, block: Box::new(*block.data()), refused: Vec::new() },
+            None => illegal.unwrap_or(SdmonError::Unsupported),
+        });
+    }
+
+    // Enable and read go out as one batch; when the enable fails or is refused
+    // in read-only mode the read is still tried on its own, some cards answer
+    // it without the enable.
+    let mut enable_refused = None;
+    let smart_res = match cmd56_write_read(transport, CMD56_SMART_ENABLE, CMD56_SMART_READ, &policy.options, debug) {
+        Err(err @ (SdmonError::Ioctl { arg: CMD56_SMART_ENABLE, .. } | SdmonError::IllegalCommand { arg: CMD56_SMART_ENABLE, .. }
+            | SdmonError::WriteRefused { arg: CMD56_SMART_ENABLE, .. })) => {
+            if debug {
+                println!("CMD56 1st CALL FAILED: {}", err);
+            }
+            if let SdmonError::WriteRefused { .. } = err {
+                enable_refused = Some(err);
+            }
+            cmd56_data_in(transport, CMD56_SMART_READ, &policy.options, debug)
+        }
+        res => res,
+    };
+
+    let (data_in, status) = match smart_res {
+        Ok(read) => read,
+        Err(err) => {
+            if debug {
+                println!("CMD56 2nd CALL FAILED: {}", err);
+            }
+            return Err(match (unknown, err) {
+                (Some((cmd56_arg, block)), _) => SdmonError::UnknownSignature { cmd56_arg, block: Box::new(*block.data()), refused: Vec::new() },
+                (None, err @ SdmonError::Ioctl { errno: Errno::EPERM | Errno::EACCES | Errno::ENOTTY, .. }) => err,
+                (None, err @ SdmonError::WriteRefused { .. }) => err,
+                (None, err @ SdmonError::IllegalCommand { .. }) => err,
+                // The read alone failed, the refused enable is the reason to report.
+                (None, _) => enable_refused.unwrap_or(SdmonError::Unsupported),
+            });
+        }
+    };
+
+    let report = get_smartdata_parser().parse(data_in.data());
+
+    return Ok(Detection::new(None, CMD56_SMART_READ, data_in, report).with_status(status));
+}
+
+/// Reads the health of an eMMC device from its EXT_CSD register and adds the
+/// vendor health report when the CID manufacturer has one. A failing vendor
+/// command is not an error, EXT_CSD alone is reported then.
+pub fn probe_emmc(transport: &mut dyn MmcTransport, registers: &CardRegisters, debug: bool) -> Result<Detection, SdmonError> {
+    return probe_emmc_with(transport, registers, &ReadOptions::new(debug));
+}
+
+/// [`probe_emmc`] with the timings of the `[[probe]]` rules and the command
+/// options of `options` on top.
+pub fn probe_emmc_with(transport: &mut dyn MmcTransport, registers: &CardRegisters, options: &ReadOptions) -> Result<Detection, SdmonError> {
+    let debug = options.debug;
+    let command_options = resolve_mmc_options(registers.cid.as_ref(), &options.probe_rules).merge(&options.command_options);
+    let ext_csd = read_ext_csd(transport, &command_options, debug)?;
+    let mid = registers.cid.as_ref().map(|cid| cid.manufacturer_id);
+    let vendor = registers.cid.as_ref().and_then(|cid| cid.manufacturer.clone()).unwrap_or_else(|| "eMMC".to_string());
+    let mut report = parse_ext_csd(&vendor, ext_csd.data());
+    let mut vendor_block = None;
+
+    if let Some(command) = mmc_vendor_command(mid) {
+        match read_vendor_report(transport, command, &command_options, debug) {
+            Ok(block) => {
+                match parse_vendor_report(command, &block) {
+                    Some(vendor_report) => merge_vendor_report(&mut report, vendor_report),
+                    None if debug => println!("{:?} health report not recognized", command),
+                    None => {}
+                }
+                vendor_block = Some(block);
+            }
+            Err(err) if debug => println!("{:?} health report failed: {}", command, err),
+            Err(_) => {}
+        }
+    }
+
+    return Ok(Detection { opcode: MMC_SEND_EXT_CSD, command: None, cmd56_arg: 0, block: ext_csd, report, registers: CardRegisters::default(), vendor_block, status: None, refused: Vec::new() });
+}
+
+/// How [`read_health_with`] talks to the card.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadOptions {
+    /// Dump every command and response.
+    pub debug: bool,
+    /// Open the device read-only and never send a command carrying data to
+    /// the card; probes which need one, like the generic SMART enable, are
+    /// skipped with [`SdmonError::WriteRefused`].
+    pub read_only: bool,
+    /// `[[probe]]` rules of the configuration, see [`policy`].
+    pub probe_rules: Vec<ProbeRule>,
+    /// Try every vendor argument instead of those of the manufacturer.
+    pub sweep_all: bool,
+    /// Timings overriding the manufacturer defaults and the rules, zero
+    /// values keep those.
+    pub command_options: CommandOptions,
+}
+
+impl ReadOptions {
+    pub fn new(debug: bool) -> Self {
+        return ReadOptions { debug, ..ReadOptions::default() };
+    }
+
+    pub fn read_only(self, read_only: bool) -> Self {
+        return ReadOptions { read_only, ..self };
+    }
+
+    pub fn probe_rules(self, probe_rules: Vec<ProbeRule>) -> Self {
+        return ReadOptions { probe_rules, ..self };
+    }
+
+    pub fn sweep_all(self, sweep_all: bool) -> Self {
+        return ReadOptions { sweep_all, ..self };
+    }
+
+    pub fn command_options(self, command_options: CommandOptions) -> Self {
+        return ReadOptions { command_options, ..self };
+    }
+}
+
+/// Opens `device` (e.g. `/dev/mmcblk0`) and reads its identification
+/// registers and health data; eMMC devices are read through EXT_CSD, SD cards
+/// through CMD56.
+pub fn read_health(device: &str, debug: bool) -> Result<Detection, SdmonError> {
+    return read_health_with(device, &ReadOptions::new(debug));
+}
+
+/// [`read_health`] with all options.
+pub fn read_health_with(device: &str, options: &ReadOptions) -> Result<Detection, SdmonError> {
+    let mut ioctl = if options.read_only { IoctlTransport::open_read_only(device)? } else { IoctlTransport::open(device)? };
+
+    // Reading the registers never writes to the card.
+    let card_dir = card_dir(device);
+    let kind = read_kind(&card_dir);
+    let registers = read_registers(&card_dir, kind, &mut ioctl);
+
+    return probe_device(&mut ioctl, kind, registers, options);
+}
+
+/// Probes the card of `kind` behind `transport` like [`read_health_with`]. In
+/// read-only mode the commands skipped are kept in [`Detection::refused`], or
+/// in the error when the probe failed with an unknown signature.
+pub fn probe_device(transport: &mut dyn MmcTransport, kind: CardKind, registers: CardRegisters, options: &ReadOptions) -> Result<Detection, SdmonError> {
+    let probe = |card: &mut dyn MmcTransport| match kind {
+        CardKind::Mmc => probe_emmc_with(card, &registers, options),
+        _ => probe_card_with(card, &registers, options),
+    };
+    let (res, refused) = if options.read_only {
+        let mut read_only = ReadOnlyTransport::new(transport);
+        let res = probe(&mut read_only);
+        (res, read_only.refused().to_vec())
+    } else {
+        (probe(transport), Vec::new())
+    };
+
+    return match res {
+        Ok(detection) => Ok(Detection { registers, refused, ..detection }),
+        Err(SdmonError::UnknownSignature { cmd56_arg, block, .. }) => Err(SdmonError::UnknownSignature { cmd56_arg, block, refused }),
+        Err(err) => Err(err),
+    };
+}
@@ -1,97 +1,552 @@
 use std::env;
-use std::os::fd::AsFd;
-use mmc_ioc_cmd::{
-    cmd56_data_in,
-    cmd56_write,
-    CMDS56,
-    SDB1
-};
-use parsers::{SDParser, get_parsers, get_smartdata_parser};
-
-
-use std::fs::File;
-use std::os::fd::AsRawFd;
+use std::net::TcpListener;
+use std::path::PathBuf;
 use std::process;
+use std::thread;
+use std::time::Duration;
 
+use sdmon::check::{evaluate, CheckResult, CheckState, Thresholds};
+use sdmon::config::{parse_duration, Config};
+use sdmon::history::{format_timestamp, now, HistoryRecord, HistoryStore, DEFAULT_HISTORY_PATH};
+use sdmon::discovery::{card_dir, discover, read_kind};
+use sdmon::error::SdmonError;
+use sdmon::json::to_json;
+use sdmon::report::CRITICAL_LIFE_PERCENT;
+use sdmon::trend::{analyze, LifeSource, NoProjection};
+use sdmon::mmc_ioc_cmd::IoctlTransport;
+use sdmon::prometheus::{render, write_textfile, DeviceSample};
+use sdmon::serve::{Exporter, DEFAULT_LISTEN};
+use sdmon::session::{RecordingTransport, Session};
+use sdmon::transport::{CommandOptions, MmcTransport, ReadOnlyTransport};
+use sdmon::registers::read_registers;
+use sdmon::{decode_block, load_block, probe_card_with, read_health_with, Detection, ReadOptions};
 
-mod mmc_ioc_cmd;
-mod parsers;
+const USAGE: &str = "\
+Usage: sdmon [-d] [--read-only] [--cmd-timeout <ms>] [--post-sleep <us>] [--config <path>] [--json] [--prom-file <path>] <device>...
+       sdmon scan [-d] [--read-only] [--cmd-timeout <ms>] [--post-sleep <us>] [--config <path>] [--json] [--prom-file <path>]
+       sdmon record [-d] [--read-only] [--cmd-timeout <ms>] [--post-sleep <us>] [--config <path>] [--sweep-all] [--json] <device> -o <session.bin>
+       sdmon replay [-d] [--json] <session.bin>
+       sdmon parse [--json] --file <dump.bin|dump.hex> [--cmd56-arg <arg>]
+       sdmon check [-d] [--read-only] [--cmd-timeout <ms>] [--post-sleep <us>] [--config <path>] [--warn-life <%>] [--crit-life <%>]
+                   [--warn-grown-bad <n>] [--crit-grown-bad <n>] [--warn-spare <n>]
+                   [--crit-spare <n>] [--warn-erase-avg <n>] [--crit-erase-avg <n>] <device>
+       sdmon daemon [-d] [--read-only] [--cmd-timeout <ms>] [--post-sleep <us>] [--config <path>] [--interval <duration>] [--history <path>] [<device>...]
+       sdmon history [--json] [--history <path>] [--field <name>]... <serial>
+       sdmon trend [--json] [--history <path>] [--eol-life <%>] <serial>
+       sdmon serve [-d] [--read-only] [--cmd-timeout <ms>] [--post-sleep <us>] [--config <path>] [--listen <addr:port>] [--min-interval <duration>] [<device>...]";
 
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(300);
 
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    debug: bool,
+    read_only: bool,
+    sweep_all: bool,
+    command_options: CommandOptions,
+    json: bool,
+    prom_file: Option<PathBuf>,
+    output: Option<PathBuf>,
+    file: Option<PathBuf>,
+    cmd56_arg: Option<u32>,
+    config: Option<PathBuf>,
+    thresholds: Thresholds,
+    interval: Option<Duration>,
+    history: Option<PathBuf>,
+    fields: Vec<String>,
+    eol_life: Option<f64>,
+    listen: Option<String>,
+    min_interval: Option<Duration>,
+}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+/// Usage errors of `check` are UNKNOWN per the plugin spec.
+fn check_usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(CheckState::Unknown.exit_code());
+}
+
+/// Parses `0x`-prefixed hex or decimal numbers.
+fn parse_u32(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Flags each subcommand accepts, as listed in [`USAGE`].
+const DEVICE_FLAGS: &[&str] = &["-d", "--read-only", "--cmd-timeout", "--post-sleep", "--config", "--json", "--prom-file"];
+const RECORD_FLAGS: &[&str] = &["-d", "--read-only", "--cmd-timeout", "--post-sleep", "--config", "--sweep-all", "--json", "-o", "--output"];
+const REPLAY_FLAGS: &[&str] = &["-d", "--json"];
+const PARSE_FLAGS: &[&str] = &["--json", "--file", "--cmd56-arg"];
+const CHECK_FLAGS: &[&str] = &[
+    "-d", "--read-only", "--cmd-timeout", "--post-sleep", "--config", "--warn-life", "--crit-life", "--warn-grown-bad",
+    "--crit-grown-bad", "--warn-spare", "--crit-spare", "--warn-erase-avg", "--crit-erase-avg",
+];
+const DAEMON_FLAGS: &[&str] = &["-d", "--read-only", "--cmd-timeout", "--post-sleep", "--config", "--interval", "--history"];
+const HISTORY_FLAGS: &[&str] = &["--json", "--history", "--field"];
+const TREND_FLAGS: &[&str] = &["--json", "--history", "--eol-life"];
+const SERVE_FLAGS: &[&str] = &["-d", "--read-only", "--cmd-timeout", "--post-sleep", "--config", "--listen", "--min-interval"];
+
+/// Parses the command line, `None` on malformed arguments or a flag not in
+/// `flags`.
+fn parse_options(args: impl Iterator<Item = String>, flags: &[&str]) -> Option<Options> {
+    let mut options = Options::default();
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') && !flags.contains(&arg.as_str()) {
+            return None;
+        }
+        let t = &mut options.thresholds;
+        match arg.as_str() {
+            "-d" => options.debug = true,
+            "--read-only" => options.read_only = true,
+            "--sweep-all" => options.sweep_all = true,
+            "--cmd-timeout" => options.command_options = options.command_options.cmd_timeout_ms(args.next()?.parse().ok()?),
+            "--post-sleep" => {
+                let us = args.next()?.parse().ok()?;
+                options.command_options = options.command_options.post_sleep_us(us, us);
+            }
+            "--json" => options.json = true,
+            "--prom-file" => options.prom_file = Some(PathBuf::from(args.next()?)),
+            "-o" | "--output" => options.output = Some(PathBuf::from(args.next()?)),
+            "--file" => options.file = Some(PathBuf::from(args.next()?)),
+            "--cmd56-arg" => options.cmd56_arg = Some(parse_u32(&args.next()?)?),
+            "--config" => options.config = Some(PathBuf::from(args.next()?)),
+            "--warn-life" => t.warn_life = Some(args.next()?.parse().ok()?),
+            "--crit-life" => t.crit_life = Some(args.next()?.parse().ok()?),
+            "--warn-grown-bad" => t.warn_grown_bad = Some(args.next()?.parse().ok()?),
+            "--crit-grown-bad" => t.crit_grown_bad = Some(args.next()?.parse().ok()?),
+            "--warn-spare" => t.warn_spare = Some(args.next()?.parse().ok()?),
+            "--crit-spare" => t.crit_spare = Some(args.next()?.parse().ok()?),
+            "--warn-erase-avg" => t.warn_erase_avg = Some(args.next()?.parse().ok()?),
+            "--crit-erase-avg" => t.crit_erase_avg = Some(args.next()?.parse().ok()?),
+            "--interval" => options.interval = Some(parse_duration(&args.next()?)?),
+            "--history" => options.history = Some(PathBuf::from(args.next()?)),
+            "--field" => options.fields.push(args.next()?),
+            "--eol-life" => options.eol_life = Some(args.next()?.parse().ok()?),
+            "--listen" => options.listen = Some(args.next()?),
+            "--min-interval" => options.min_interval = Some(parse_duration(&args.next()?)?),
+            _ if arg.starts_with('-') => return None,
+            _ => options.positional.push(arg),
+        }
+    }
+
+    Some(options)
+}
+
+fn read_options(options: &Options, config: &Config) -> ReadOptions {
+    ReadOptions::new(options.debug)
+        .read_only(options.read_only)
+        .sweep_all(options.sweep_all)
+        .probe_rules(config.probe.clone())
+        .command_options(options.command_options)
+}
+
+fn print_detection(device: &str, detection: &Detection, json: bool) {
+    if json {
+        println!("{}", to_json(device, detection));
+    } else {
+        print!("{}{}", detection.registers, detection.report);
+    }
+}
+
+/// Prints the result of one device and returns its exit code.
+fn report_result(device: &str, result: &Result<Detection, SdmonError>, options: &Options) -> i32 {
+    if let Ok(detection) = result {
+        for command in &detection.refused {
+            eprintln!("Device {}: {}", device, SdmonError::WriteRefused { opcode: command.opcode, arg: command.arg });
+        }
+    }
+
+    match result {
+        Ok(_) if options.prom_file.is_some() => 0,
+        Ok(detection) => {
+            print_detection(device, detection, options.json);
+            0
+        }
+        Err(err) => {
+            eprintln!("Device {} error: {}", device, err);
+            err.exit_code()
+        }
+    }
+}
+
+/// Reads and prints the health of every device; text output gets a
+/// `Device:` header line when `headers` is set.
+fn run_devices(devices: &[String], options: &Options, headers: bool) -> i32 {
+    let config = match load_config(options) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return err.exit_code();
+        }
+    };
+    let read_options = read_options(options, &config);
+    let mut exit_code = 0;
+    let mut results = Vec::new();
+
+    for device in devices {
+        if options.debug {
+            dbg!(device);
+        }
+        if headers && !options.json && options.prom_file.is_none() {
+            println!("Device: {}", device);
+        }
 
-    if args.len() < 2 {
-        println!("Usage: sdmon <device>");
-        process::exit(0);
+        let result = read_health_with(device, &read_options);
+        let code = report_result(device, &result, options);
+        if code != 0 {
+            exit_code = code;
+        }
+        results.push(result);
     }
 
-    let mut device: String = "".to_string();
-    let mut debug_flag = false;
+    if let Some(path) = &options.prom_file {
+        let serials: Vec<String> = results.iter()
+            .map(|result| result.as_ref().ok().and_then(|detection| detection.registers.serial()).unwrap_or_default())
+            .collect();
+        let samples: Vec<DeviceSample> = devices.iter().zip(&results).zip(&serials)
+            .map(|((device, result), serial)| DeviceSample {
+                device,
+                serial,
+                report: result.as_ref().ok().map(|detection| &detection.report),
+            })
+            .collect();
 
-    for arg in args {
-        if arg.starts_with('/')
-        {
-            device.clone_from(&arg);
+        if let Err(err) = write_textfile(path, &render(&samples)) {
+            eprintln!("Writing {} failed: {}", path.display(), err);
+            exit_code = 1;
         }
-        if arg == "-d" {
-            debug_flag = true;
+    }
+
+    exit_code
+}
+
+/// Block devices of all discovered SD and eMMC cards.
+fn discovered_devices() -> Vec<String> {
+    let mut devices = Vec::new();
+    for card in discover() {
+        match card.device() {
+            Some(device) if card.kind.has_storage() => devices.push(device),
+            _ => eprintln!("Skipping {} card {}", card.kind.name(), card.sysfs_path.display()),
         }
     }
+    devices
+}
 
-    dbg!(&device);
+fn run_scan(options: &Options) -> i32 {
+    if !options.positional.is_empty() {
+        usage_error();
+    }
 
-    let ff = File::options().read(true).write(true).open(&device);
+    let devices = discovered_devices();
 
-    if ff.is_err() {
-        println!("Device {} opening error: {}", &device, ff.err().unwrap());
-        process::exit(1);
+    if devices.is_empty() {
+        eprintln!("No MMC/SD cards found");
     }
 
-    let fl = ff.unwrap(); // TODO check result here
-    let fd = fl.as_fd();
-    let rfd = fd.as_raw_fd();
+    run_devices(&devices, options, true)
+}
 
-    let _data_in: SDB1 = SDB1::new(); //&SDBlock = SDBlock::get_instance();
+fn run_record(options: &Options) -> i32 {
+    let (Some(device), Some(output), 1) = (options.positional.first(), &options.output, options.positional.len()) else {
+        usage_error();
+    };
 
-    for cmd  in CMDS56 {
+    let opened = if options.read_only { IoctlTransport::open_read_only(device) } else { IoctlTransport::open(device) };
+    let mut ioctl = match opened {
+        Ok(transport) => transport,
+        Err(err) => return report_result(device, &Err(err), options),
+    };
+    let mut read_only;
+    let card: &mut dyn MmcTransport = if options.read_only {
+        read_only = ReadOnlyTransport::new(&mut ioctl);
+        &mut read_only
+    } else {
+        &mut ioctl
+    };
+    let config = match load_config(options) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return err.exit_code();
+        }
+    };
+
+    // The registers only pick the policy, the session holds the probe itself.
+    let card_dir = card_dir(device);
+    let registers = read_registers(&card_dir, read_kind(&card_dir), card);
+    let mut transport = RecordingTransport::new(device, card);
+    let result = probe_card_with(&mut transport, &registers, &read_options(options, &config));
+
+    // The session is saved even when probing failed, unknown cards are
+    // exactly what it is for.
+    if let Err(err) = transport.session().save(output) {
+        eprintln!("{}", err);
+        return err.exit_code();
+    }
+
+    report_result(device, &result, options)
+}
 
-        let cmd_value = cmd as u32;
-        let cmd56_data_in_res = cmd56_data_in(rfd, cmd_value, &_data_in, debug_flag);
+fn run_replay(options: &Options) -> i32 {
+    let [path] = options.positional.as_slice() else {
+        usage_error();
+    };
 
-        if cmd56_data_in_res.is_ok() {
-            let parsers_vec: Vec<Box<dyn SDParser>> = get_parsers();
+    let session = match Session::load(path.as_ref()) {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("{}", err);
+            return err.exit_code();
+        }
+    };
+
+    let result = session.probe(options.debug);
+    report_result(&session.device, &result, options)
+}
+
+fn run_parse(options: &Options) -> i32 {
+    let (Some(path), true) = (&options.file, options.positional.is_empty()) else {
+        usage_error();
+    };
 
-            for parser in parsers_vec {
-                if parser.check_signature(cmd, _data_in.data())
-                {
-                    parser.dump_data(_data_in.data());
-                    process::exit(0);
+    let result = load_block(path).and_then(|block| decode_block(options.cmd56_arg, block));
+    report_result(&path.display().to_string(), &result, options)
+}
+
+fn load_config(options: &Options) -> Result<Config, SdmonError> {
+    match &options.config {
+        Some(path) => Config::load(path),
+        None => Ok(Config::default()),
+    }
+}
+
+fn run_check(options: &Options) -> i32 {
+    let [device] = options.positional.as_slice() else {
+        check_usage_error();
+    };
+
+    let config = match load_config(options) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("{}", CheckResult::unknown(&err.to_string()));
+            return CheckState::Unknown.exit_code();
+        }
+    };
+
+    let result = match read_health_with(device, &read_options(options, &config)) {
+        Ok(detection) => {
+            let serial = detection.registers.serial();
+            let thresholds = config.thresholds(device, serial.as_deref(), &detection.report.vendor)
+                .merge(&options.thresholds);
+            evaluate(&detection.report, &thresholds)
+        }
+        Err(err) => CheckResult::unknown(&format!("{}: {}", device, err)),
+    };
+
+    println!("{}", result);
+    result.state.exit_code()
+}
+
+fn run_daemon(options: &Options) -> i32 {
+    let config = match load_config(options) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return err.exit_code();
+        }
+    };
+
+    let configured_interval = config.daemon.interval.as_deref().map(|text| parse_duration(text).ok_or(text));
+    let interval = match (options.interval, configured_interval) {
+        (Some(interval), _) => interval,
+        (None, Some(Ok(interval))) => interval,
+        (None, Some(Err(text))) => {
+            eprintln!("Invalid daemon interval '{}'", text);
+            return 1;
+        }
+        (None, None) => DEFAULT_INTERVAL,
+    };
+    let history_path = options.history.clone()
+        .or(config.daemon.history.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_HISTORY_PATH));
+    let store = HistoryStore::new(&history_path);
+    let configured_devices = if options.positional.is_empty() { &config.daemon.devices } else { &options.positional };
+    let read_options = read_options(options, &config);
+
+    loop {
+        let devices = if configured_devices.is_empty() { discovered_devices() } else { configured_devices.clone() };
+        let timestamp = now();
+
+        for device in &devices {
+            match read_health_with(device, &read_options) {
+                Ok(detection) => {
+                    if let Err(err) = store.append(&HistoryRecord::new(timestamp, device, &detection)) {
+                        eprintln!("{}", err);
+                    }
                 }
+                Err(err) => eprintln!("Device {} error: {}", device, err),
             }
-
-            println!("Command {:010X?} succeeded but no parser available", cmd_value);
-            println!("{}", _data_in);
         }
-        else {
-            println!("Command {:010X?} failed", cmd_value);
+
+        thread::sleep(interval);
+    }
+}
+
+/// Loads the history of the card with the serial number given on the command
+/// line, or the exit code when there is none.
+fn load_series(options: &Options) -> Result<Vec<HistoryRecord>, i32> {
+    let [serial] = options.positional.as_slice() else {
+        usage_error();
+    };
+
+    let path = options.history.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_HISTORY_PATH));
+    let records = HistoryStore::new(&path).series(serial).map_err(|err| {
+        eprintln!("{}", err);
+        err.exit_code()
+    })?;
+
+    if records.is_empty() {
+        eprintln!("No history for serial {} in {}", serial, path.display());
+        return Err(1);
+    }
+    Ok(records)
+}
+
+fn run_history(options: &Options) -> i32 {
+    let records = match load_series(options) {
+        Ok(records) => records,
+        Err(code) => return code,
+    };
+
+    if options.json {
+        for record in &records {
+            println!("{}", serde_json::to_string(record).expect("history record is always serializable"));
         }
+        return 0;
     }
 
-    let cmd56_write_res = cmd56_write(rfd, 0x00000010, debug_flag);
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let mut header = format!("{:<20}  {:<16}  {:>6}  {:>8}  {:>9}  {:>9}", "Time", "Device", "Life", "Status", "Erase avg", "Grown bad");
+    for field in &options.fields {
+        header.push_str(&format!("  {:>12}", field));
+    }
+    println!("{}", header);
 
-    if cmd56_write_res.is_err() {
-        println!("CMD56 1st CALL FAILED: {}", cmd56_write_res.err().unwrap());
+    for record in &records {
+        let report = &record.report;
+        let mut line = format!("{:<20}  {:<16}  {:>6}  {:>8}  {:>9}  {:>9}",
+            format_timestamp(record.timestamp),
+            record.device,
+            optional(report.remaining_life_percent.map(|life| format!("{}%", life))),
+            report.status.name(),
+            optional(report.erase_count.avg.map(|avg| avg.to_string())),
+            optional(report.bad_blocks.grown.map(|grown| grown.to_string())));
+        for field in &options.fields {
+            line.push_str(&format!("  {:>12}", optional(report.field(field).map(|field| field.value.to_string()))));
+        }
+        println!("{}", line);
     }
 
-    let cmd56_read_smart_data_res = cmd56_data_in(rfd, 0x00000021, &_data_in, debug_flag);
+    0
+}
+
+fn run_trend(options: &Options) -> i32 {
+    let records = match load_series(options) {
+        Ok(records) => records,
+        Err(code) => return code,
+    };
+    let eol_life = options.eol_life.unwrap_or(f64::from(CRITICAL_LIFE_PERCENT));
+    let trend = analyze(&records, eol_life).expect("load_series rejects an empty series");
+
+    if options.json {
+        println!("{}", serde_json::to_string(&trend).expect("trend is always serializable"));
+        if let Some(reason) = trend.no_projection() {
+            eprintln!("No projection: {}", reason);
+        }
+        return 0;
+    }
 
-    if cmd56_read_smart_data_res.is_err() {
-        println!("CMD56 2nd CALL FAILED: {}", cmd56_read_smart_data_res.err().unwrap());
-        process::exit(0);
+    println!("Samples: {} ({} - {})", trend.samples, format_timestamp(trend.first), format_timestamp(trend.last));
+    match (trend.life_percent, trend.life_source) {
+        (Some(life), Some(LifeSource::RatedCycles)) => println!("Remaining life: {:.2}% (average erase count vs. rated cycles)", life),
+        (Some(life), _) => println!("Remaining life: {:.2}%", life),
+        (None, _) => println!("Remaining life: not reported"),
+    }
+    if let Some(rate) = trend.life_per_day {
+        println!("Wear rate: {:.4}% per day", rate);
+    }
+    if let Some(rate) = trend.written_tb_per_day {
+        println!("Written: {:.4} TB per day", rate);
     }
-    else {
-        get_smartdata_parser().dump_data(_data_in.data())
+    match trend.eol_timestamp {
+        Some(eol) => println!("Projected {}% remaining life: {}", eol_life, format_timestamp(eol)),
+        None => println!("Projected {}% remaining life: none, {}", eol_life,
+            trend.no_projection().unwrap_or(NoProjection::NoMeasurableWear)),
     }
-}
\ No newline at end of file
+
+    0
+}
+
+fn run_serve(options: &Options) -> i32 {
+    let devices = if options.positional.is_empty() { discovered_devices() } else { options.positional.clone() };
+    let listen = options.listen.as_deref().unwrap_or(DEFAULT_LISTEN);
+
+    let listener = match TcpListener::bind(listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Listening on {} failed: {}", listen, err);
+            return 1;
+        }
+    };
+
+    let config = match load_config(options) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return err.exit_code();
+        }
+    };
+    let read_options = read_options(options, &config);
+    let mut exporter = Exporter::new(
+        devices,
+        options.min_interval.unwrap_or(DEFAULT_MIN_INTERVAL),
+        Box::new(move |device: &str| read_health_with(device, &read_options)),
+    );
+    exporter.serve(&listener);
+    0
+}
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+
+    let exit_code = match args.peek().map(String::as_str) {
+        None => {
+            println!("{}", USAGE);
+            0
+        }
+        Some("record") => run_record(&parse_options(args.skip(1), RECORD_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("replay") => run_replay(&parse_options(args.skip(1), REPLAY_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("parse") => run_parse(&parse_options(args.skip(1), PARSE_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("scan") => run_scan(&parse_options(args.skip(1), DEVICE_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("daemon") => run_daemon(&parse_options(args.skip(1), DAEMON_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("history") => run_history(&parse_options(args.skip(1), HISTORY_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("trend") => run_trend(&parse_options(args.skip(1), TREND_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("serve") => run_serve(&parse_options(args.skip(1), SERVE_FLAGS).unwrap_or_else(|| usage_error())),
+        Some("check") => run_check(&parse_options(args.skip(1), CHECK_FLAGS).unwrap_or_else(|| check_usage_error())),
+        Some(_) => {
+            let options = parse_options(args, DEVICE_FLAGS).unwrap_or_else(|| usage_error());
+            run_devices(&options.positional, &options, options.positional.len() > 1)
+        }
+    };
+
+    process::exit(exit_code);
+}
@@ -0,0 +1,105 @@
+//! CID manufacturer IDs and the health commands known to work for them.
+
+use crate::emmc::EmmcVendorCommand;
+use crate::mmc_ioc_cmd::Cmd56;
+use crate::mmc_ioc_cmd::Cmd56::*;
+use crate::transport::CommandOptions;
+
+/// How to read the health data of a manufacturer's cards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProbeStrategy {
+    /// Try these vendor commands, then the generic SMART sequence.
+    Vendor(&'static [Cmd56]),
+    /// Only the generic SMART sequence.
+    GenericSmart,
+    /// No known command, sweep all of `CMDS56`.
+    Sweep,
+}
+
+pub struct Manufacturer {
+    pub mid: u8,
+    pub name: &'static str,
+    pub strategy: ProbeStrategy,
+}
+
+/// Manufacturer IDs of SD cards. Most of the CMD56 arguments in the
+/// `0x110005xx` range belong to Phison controllers, which is why Phison
+/// branded cards try all of them.
+pub const SD_MANUFACTURERS: &[Manufacturer] = &[
+    Manufacturer { mid: 0x01, name: "Panasonic", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x02, name: "Toshiba", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x03, name: "SanDisk", strategy: ProbeStrategy::Vendor(&[Sandisk]) },
+    Manufacturer { mid: 0x09, name: "ATP", strategy: ProbeStrategy::Vendor(&[Atp]) },
+    Manufacturer { mid: 0x1B, name: "Samsung", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x1D, name: "ADATA", strategy: ProbeStrategy::Vendor(&[AData]) },
+    Manufacturer { mid: 0x27, name: "Phison", strategy: ProbeStrategy::Vendor(&[Micron, Transcend, LongsysM9H, AData]) },
+    Manufacturer { mid: 0x28, name: "Lexar", strategy: ProbeStrategy::Vendor(&[Sandisk, LongsysM9H]) },
+    Manufacturer { mid: 0x31, name: "Silicon Power", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x41, name: "Kingston", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x5D, name: "Swissbit", strategy: ProbeStrategy::Vendor(&[Swissbit]) },
+    Manufacturer { mid: 0x74, name: "Transcend", strategy: ProbeStrategy::Vendor(&[Transcend]) },
+    Manufacturer { mid: 0x76, name: "Patriot", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x82, name: "Sony", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x9C, name: "Angelbird", strategy: ProbeStrategy::Sweep },
+    Manufacturer { mid: 0x9F, name: "Longsys", strategy: ProbeStrategy::Vendor(&[Sandisk, LongsysM9H]) },
+];
+
+pub fn lookup_sd(mid: u8) -> Option<&'static Manufacturer> {
+    return SD_MANUFACTURERS.iter().find(|manufacturer| manufacturer.mid == mid);
+}
+
+/// Strategy for an SD card with manufacturer ID `mid`, `Sweep` when the ID
+/// is unknown.
+pub fn sd_strategy(mid: Option<u8>) -> ProbeStrategy {
+    return mid.and_then(lookup_sd).map(|manufacturer| manufacturer.strategy).unwrap_or(ProbeStrategy::Sweep);
+}
+
+/// Command options of every built-in SD policy: transient errors are
+/// retried twice.
+pub const SD_COMMAND_OPTIONS: CommandOptions = CommandOptions::new().retries(2);
+
+/// Industrial cards which take a while after CMD56 until the data block is
+/// ready.
+pub const SD_SLOW_MANUFACTURERS: &[(u8, CommandOptions)] = &[
+    (0x09, SD_COMMAND_OPTIONS.post_sleep_us(200_000, 250_000).cmd_timeout_ms(1000)), // ATP
+    (0x5D, SD_COMMAND_OPTIONS.post_sleep_us(200_000, 250_000).cmd_timeout_ms(1000)), // Swissbit
+];
+
+/// Command options for an SD card with manufacturer ID `mid`.
+pub fn sd_command_options(mid: Option<u8>) -> CommandOptions {
+    return SD_SLOW_MANUFACTURERS.iter()
+        .find(|(slow, _)| Some(*slow) == mid)
+        .map(|(_, options)| *options)
+        .unwrap_or(SD_COMMAND_OPTIONS);
+}
+
+/// Command options of eMMC devices.
+pub const MMC_COMMAND_OPTIONS: CommandOptions = CommandOptions::new().retries(2);
+
+/// Manufacturer of an eMMC device and its vendor health command, if any.
+pub struct MmcManufacturer {
+    pub mid: u8,
+    pub name: &'static str,
+    pub command: Option<EmmcVendorCommand>,
+}
+
+/// Manufacturer IDs of eMMC devices, which are assigned independently of the
+/// SD IDs.
+pub const MMC_MANUFACTURERS: &[MmcManufacturer] = &[
+    MmcManufacturer { mid: 0x11, name: "Toshiba", command: None },
+    MmcManufacturer { mid: 0x13, name: "Micron", command: Some(EmmcVendorCommand::Micron) },
+    MmcManufacturer { mid: 0x15, name: "Samsung", command: Some(EmmcVendorCommand::Samsung) },
+    MmcManufacturer { mid: 0x45, name: "SanDisk", command: Some(EmmcVendorCommand::Sandisk) },
+    MmcManufacturer { mid: 0x70, name: "Kingston", command: Some(EmmcVendorCommand::Phison) },
+    MmcManufacturer { mid: 0x90, name: "SK Hynix", command: None },
+    MmcManufacturer { mid: 0xFE, name: "Micron", command: Some(EmmcVendorCommand::Micron) },
+];
+
+pub fn lookup_mmc(mid: u8) -> Option<&'static MmcManufacturer> {
+    return MMC_MANUFACTURERS.iter().find(|manufacturer| manufacturer.mid == mid);
+}
+
+/// Vendor health command of an eMMC device with manufacturer ID `mid`.
+pub fn mmc_vendor_command(mid: Option<u8>) -> Option<EmmcVendorCommand> {
+    return mid.and_then(lookup_mmc).and_then(|manufacturer| manufacturer.command);
+}
@@ -1,9 +1,17 @@
 #![allow(dead_code)]
 
-use nix::ioctl_readwrite;
 use nix::errno::Errno;
+use nix::{ioctl_readwrite, ioctl_readwrite_bad, request_code_readwrite};
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fs::File;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use std::os::fd::AsRawFd;
+use crate::error::SdmonError;
+use crate::status::CardStatus;
+use crate::transport::{send_each, CommandOptions, MmcCommand, MmcResponse, MmcTransport};
 use crate::mmc_ioc_cmd::Cmd56::*;
 
 const MMC_RSP_PRESENT: u32 = 1 << 0;
@@ -25,15 +33,27 @@ const MMC_RSP_NONE: u32 = 0;
 pub const MMC_RSP_R1: u32 = MMC_RSP_PRESENT | MMC_RSP_CRC | MMC_RSP_OPCODE;
 const MMC_RSP_R1B: u32 = MMC_RSP_PRESENT | MMC_RSP_CRC | MMC_RSP_OPCODE | MMC_RSP_BUSY;
 
-pub const COMMAND_FLAGS_CMD56_DATA_IN: u32 = MMC_RSP_SPI_R1 | MMC_RSP_R1 | MMC_CMD_ADTC; // 181
+pub const COMMAND_FLAGS_DATA_IN: u32 = MMC_RSP_SPI_R1 | MMC_RSP_R1 | MMC_CMD_ADTC; // 181
+pub const COMMAND_FLAGS_CMD56_DATA_IN: u32 = COMMAND_FLAGS_DATA_IN;
 pub const COMMAND_FLAGS_CMD56_WRITE: u32 = MMC_RSP_R1 | MMC_CMD_ADTC;
+pub const COMMAND_FLAGS_AC_R1B: u32 = MMC_RSP_SPI_R1B | MMC_RSP_R1B | MMC_CMD_AC;
 
 pub const SD_BLOCK_SIZE: usize = 512;
 
 const MMC_BLOCK_MAJOR: u8 = 0xB3;
-const SD_GEN_CMD: u32 = 56;
+pub const SD_GEN_CMD: u32 = 56;
 
 ioctl_readwrite!(mmc_ioc_cmd_rw, MMC_BLOCK_MAJOR, 0, MmcIocCmd);
+// The request code encodes the size of the header only, the commands follow it.
+ioctl_readwrite_bad!(mmc_ioc_multi_cmd_rw, request_code_readwrite!(MMC_BLOCK_MAJOR, 1, std::mem::size_of::<u64>()), MmcIocMultiCmd);
+
+/// Most commands [`IoctlTransport`] submits in one MMC_IOC_MULTI_CMD batch,
+/// longer batches are sent one by one.
+pub const MMC_IOC_MAX_BATCH: usize = 8;
+
+/// Pause before the first repetition of a command, doubled for every further
+/// one.
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
 
 pub type SDBlock = [u8; SD_BLOCK_SIZE];
 
@@ -45,11 +65,31 @@ impl SDB1 {
     pub fn new() -> Self {
         return SDB1{data: [0; SD_BLOCK_SIZE]};
     }
+    /// Copies `data` into a new block, truncating or zero padding it to
+    /// `SD_BLOCK_SIZE` bytes.
+    pub fn from_slice(data: &[u8]) -> Self {
+        let mut block = SDB1::new();
+        let len = data.len().min(SD_BLOCK_SIZE);
+        block.data[..len].copy_from_slice(&data[..len]);
+        return block;
+    }
     pub fn data(&self) -> &SDBlock {
         return &(self.data);
     }
 }
 
+impl From<SDBlock> for SDB1 {
+    fn from(data: SDBlock) -> Self {
+        return SDB1{data};
+    }
+}
+
+impl Default for SDB1 {
+    fn default() -> Self {
+        return SDB1::new();
+    }
+}
+
 impl Display for SDB1 {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> { 
         _ = writeln!(f, "=== Begin buffer dump ===");
@@ -63,8 +103,34 @@ impl Display for SDB1 {
     }
 }
 
+impl FromStr for SDB1 {
+    type Err = SdmonError;
+
+    /// Parses the `=== Begin buffer dump ===` format produced by `Display`,
+    /// or plain hex bytes separated by whitespace.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut text = text;
+        if let Some(begin) = text.find("=== Begin buffer dump ===") {
+            text = &text[begin + "=== Begin buffer dump ===".len()..];
+            text = &text[..text.find("=== End buffer dump ===").unwrap_or(text.len())];
+        }
+
+        let mut bytes = Vec::with_capacity(SD_BLOCK_SIZE);
+        for token in text.split_whitespace() {
+            let byte = u8::from_str_radix(token, 16)
+                .map_err(|_| SdmonError::Parse(format!("invalid hex byte '{}'", token)))?;
+            bytes.push(byte);
+        }
+
+        if bytes.len() != SD_BLOCK_SIZE {
+            return Err(SdmonError::Parse(format!("expected {} bytes, found {}", SD_BLOCK_SIZE, bytes.len())));
+        }
+        return Ok(SDB1::from_slice(&bytes));
+    }
+}
+
 // #[derive(FromPrimitive)]
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Cmd56 {
     
     Sandisk = 0x00000001, // Sandisk, Longsys
@@ -76,6 +142,14 @@ pub enum Cmd56 {
     AData = 0x110005f1
 }
 
+impl TryFrom<u32> for Cmd56 {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        return CMDS56.into_iter().find(|cmd| *cmd as u32 == value).ok_or(value);
+    }
+}
+
 pub const CMDS56: [Cmd56; 7] = [Sandisk, Micron, Swissbit, Transcend, LongsysM9H, Atp, AData];
 
 pub trait GetInstance<'sdb, T> {
@@ -89,8 +163,8 @@ impl<'sdb> GetInstance<'sdb, SDBlock> for SDBlock {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct MmcIocCmd {
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MmcIocCmd {
     pub write_flag: cty::c_int,
     pub is_acmd: cty::c_int,
     pub opcode: cty::c_uint,
@@ -108,7 +182,7 @@ struct MmcIocCmd {
 }
 
 impl MmcIocCmd {
-    pub fn new(cmd_write_flag: i32, cmd_opcode:u32, cmd_arg: u32, cmd_flags: u32, lba_block_data: &SDBlock) -> Self {
+    pub fn new(cmd_write_flag: i32, cmd_opcode:u32, cmd_arg: u32, cmd_flags: u32, lba_block_data: &mut [u8]) -> Self {
         Self { 
             write_flag : cmd_write_flag, 
             is_acmd : 0, 
@@ -116,60 +190,269 @@ impl MmcIocCmd {
             arg : cmd_arg,
             response : [0; 4], 
             flags : cmd_flags, 
-            blksz : SD_BLOCK_SIZE as u32, 
-            blocks : 1,
+            blksz : lba_block_data.len() as u32, 
+            blocks : if lba_block_data.is_empty() { 0 } else { 1 },
             postsleep_min_us : 0, 
             postsleep_max_us : 0,
             data_timeout_ns : 0, 
             cmd_timeout_ms : 0,
             __pad : 0, 
-            data_ptr : lba_block_data as *const u8 as u64 }
-    }
-}
-
-// pub fn dump_buf(buf: &SDBlock) {
-//     println!("=== Begin buffer dump ===");
-//     for i in 0..buf.len() {
-//         print!("{:02X?} ", buf[i]);
-//         if (i+1) % 16 == 0 {
-//             println!();
-//         }
-//     }
-//     println!("=== End buffer dump ===");
-// }
-
-pub fn cmd56_data_in(fdesc: i32, cmd56_arg: u32, lba_block_data: &SDB1, debug: bool) -> Result<i32, Errno> {
-    let mut command: MmcIocCmd = MmcIocCmd::new(0, SD_GEN_CMD, 
-        cmd56_arg, COMMAND_FLAGS_CMD56_DATA_IN, lba_block_data.data());
-        
-    unsafe {
-        let res = mmc_ioc_cmd_rw(fdesc, &mut command/* as *mut _ */);
-        if debug {
-            dbg!(command);
-            if res.is_ok() {
-                println!("{}", lba_block_data);
-                // dump_buf(lba_block_data);
-            }
+            data_ptr : lba_block_data.as_mut_ptr() as u64 }
+    }
+}
+
+/// Argument of MMC_IOC_MULTI_CMD, `struct mmc_ioc_multi_cmd` with room for
+/// [`MMC_IOC_MAX_BATCH`] commands.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MmcIocMultiCmd {
+    pub num_of_cmds: u64,
+    pub cmds: [MmcIocCmd; MMC_IOC_MAX_BATCH],
+}
+
+fn ioc_cmd(command: &MmcCommand, data: &mut [u8]) -> MmcIocCmd {
+    let mut ioc = MmcIocCmd::new(command.write as i32, command.opcode,
+        command.arg, command.flags, data);
+    ioc.is_acmd = command.is_acmd as i32;
+    ioc.blksz = command.blksz;
+    ioc.blocks = command.blocks;
+    ioc.postsleep_min_us = command.options.postsleep_min_us;
+    ioc.postsleep_max_us = command.options.postsleep_max_us;
+    ioc.data_timeout_ns = command.options.data_timeout_ns;
+    ioc.cmd_timeout_ms = command.options.cmd_timeout_ms;
+    return ioc;
+}
+
+fn data_buffer(command: &MmcCommand) -> Vec<u8> {
+    let mut data = command.data.clone();
+    data.resize(command.data_len(), 0);
+    return data;
+}
+
+/// [`MmcTransport`] issuing the `mmc_ioc_cmd_rw` ioctl on an MMC block device.
+///
+/// Batches go through MMC_IOC_MULTI_CMD, which the kernel runs while holding
+/// the card; kernels before 4.4 lack it and get the commands one by one.
+pub struct IoctlTransport {
+    file: File,
+    multi_cmd: bool,
+}
+
+impl IoctlTransport {
+    pub fn new(file: File) -> Self {
+        return IoctlTransport { file, multi_cmd: true };
+    }
+
+    /// Opens `device` (e.g. `/dev/mmcblk0`) for issuing commands.
+    pub fn open(device: &str) -> Result<Self, SdmonError> {
+        let file = File::options().read(true).write(true).open(device)
+            .map_err(|err| SdmonError::open(device, err))?;
+        return Ok(IoctlTransport::new(file));
+    }
+
+    /// Opens `device` without write access; MMC_IOC_CMD only needs
+    /// CAP_SYS_RAWIO, not a writable descriptor.
+    pub fn open_read_only(device: &str) -> Result<Self, SdmonError> {
+        let file = File::options().read(true).open(device)
+            .map_err(|err| SdmonError::open(device, err))?;
+        return Ok(IoctlTransport::new(file));
+    }
+}
+
+impl MmcTransport for IoctlTransport {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError> {
+        let mut data = data_buffer(command);
+        let mut ioc = ioc_cmd(command, &mut data);
+
+        unsafe {
+            mmc_ioc_cmd_rw(self.file.as_raw_fd(), &mut ioc)
+                .map_err(|errno| SdmonError::Ioctl { opcode: command.opcode, arg: command.arg, errno })?;
+        }
+
+        return Ok(MmcResponse { response: ioc.response, data });
+    }
+
+    fn send_batch(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+        return send_multi_cmd(self, commands);
+    }
+}
+
+impl MultiCmdTransport for IoctlTransport {
+    fn multi_cmd(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, Errno> {
+        let mut buffers: Vec<Vec<u8>> = commands.iter().map(data_buffer).collect();
+        let mut multi = MmcIocMultiCmd { num_of_cmds: commands.len() as u64, cmds: [MmcIocCmd::default(); MMC_IOC_MAX_BATCH] };
+        for (index, (command, data)) in commands.iter().zip(buffers.iter_mut()).enumerate() {
+            multi.cmds[index] = ioc_cmd(command, data);
         }
 
-        return res;
+        unsafe {
+            mmc_ioc_multi_cmd_rw(self.file.as_raw_fd(), &mut multi)?;
+        }
+
+        return Ok(multi.cmds.iter().zip(buffers)
+            .map(|(ioc, data)| MmcResponse { response: ioc.response, data })
+            .collect());
+    }
+
+    fn multi_cmd_supported(&self) -> bool {
+        return self.multi_cmd;
+    }
+
+    fn set_multi_cmd_supported(&mut self, supported: bool) {
+        self.multi_cmd = supported;
     }
 }
 
-pub fn cmd56_write(fdesc: i32, cmd56_arg: u32, debug: bool) -> Result<i32, Errno> {
-    let lba_block_data: SDB1 = SDB1::new();
+/// Transport with a native batch, MMC_IOC_MULTI_CMD, which kernels before
+/// 4.4 do not have.
+pub trait MultiCmdTransport: MmcTransport {
+    /// Sends `commands`, at most [`MMC_IOC_MAX_BATCH`], as one batch.
+    fn multi_cmd(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, Errno>;
 
-    let mut command: MmcIocCmd = MmcIocCmd::new(1, SD_GEN_CMD, 
-        cmd56_arg, COMMAND_FLAGS_CMD56_WRITE, lba_block_data.data());
+    fn multi_cmd_supported(&self) -> bool;
 
-    unsafe {
-        let res = mmc_ioc_cmd_rw(fdesc, &mut command);
-        if debug {
-            dbg!(command);
-            if res.is_ok() {
-                println!("{}", lba_block_data);
+    fn set_multi_cmd_supported(&mut self, supported: bool);
+}
+
+/// Whether `errno` of MMC_IOC_MULTI_CMD means the kernel does not know the
+/// ioctl, ENOTTY from the block layer.
+pub fn multi_cmd_unsupported(errno: Errno) -> bool {
+    return errno == Errno::ENOTTY;
+}
+
+/// [`MmcTransport::send_batch`] of a [`MultiCmdTransport`]. Falls back to
+/// [`send_each`] for batches too large for one ioctl and, from then on, when
+/// the kernel does not know MMC_IOC_MULTI_CMD. EINVAL falls back for the
+/// batch at hand only: `mmc_blk_ioctl` of kernels before 4.4 rejects the
+/// unknown ioctl with it, but newer kernels also reject a bad command of the
+/// batch with it. A failed batch is reported as an error of its first
+/// command, the kernel does not tell which command failed.
+pub fn send_multi_cmd<T: MultiCmdTransport>(transport: &mut T, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+    let Some(first) = commands.first() else {
+        return Ok(Vec::new());
+    };
+    if !transport.multi_cmd_supported() || commands.len() > MMC_IOC_MAX_BATCH {
+        return send_each(transport, commands);
+    }
+
+    match transport.multi_cmd(commands) {
+        Ok(responses) => return Ok(responses),
+        Err(errno) if multi_cmd_unsupported(errno) => {
+            transport.set_multi_cmd_supported(false);
+            return send_each(transport, commands);
+        }
+        Err(Errno::EINVAL) => return send_each(transport, commands),
+        Err(errno) => return Err(SdmonError::Ioctl { opcode: first.opcode, arg: first.arg, errno }),
+    }
+}
+
+pub(crate) fn debug_dump(command: &MmcCommand, res: &Result<MmcResponse, SdmonError>) {
+    dbg!(command);
+    if let Ok(response) = res {
+        dbg!(response.response);
+        println!("R1 status {}", response.status());
+        println!("{}", SDB1::from_slice(&response.data));
+    }
+}
+
+/// Fails with [`SdmonError::IllegalCommand`] when the R1 status of `response`
+/// has ILLEGAL_COMMAND set; the data of such a response is whatever was in
+/// the card's buffer before.
+fn check_status(command: &MmcCommand, response: MmcResponse) -> Result<MmcResponse, SdmonError> {
+    let status = response.status();
+    if status.illegal_command() {
+        return Err(SdmonError::IllegalCommand { opcode: command.opcode, arg: command.arg, status });
+    }
+    return Ok(response);
+}
+
+/// Timeouts and CRC errors on the bus are worth another try, the card may
+/// just not have had the block ready.
+fn is_transient(err: &SdmonError) -> bool {
+    return matches!(err, SdmonError::Ioctl { errno: Errno::ETIMEDOUT | Errno::EILSEQ, .. });
+}
+
+/// Runs `send` until it succeeds, fails with a permanent error or the
+/// retries of `options` are used up.
+pub(crate) fn with_retries<T>(options: &CommandOptions, debug: bool, mut send: impl FnMut() -> Result<T, SdmonError>) -> Result<T, SdmonError> {
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match send() {
+            Err(err) if attempt < options.retries && is_transient(&err) => {
+                if debug {
+                    println!("{}, retrying in {:?}", err, backoff);
+                }
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
             }
+            res => return res,
         }
-        return res;
     }
-}
\ No newline at end of file
+}
+
+/// Sends `commands` as one batch, see [`MmcTransport::send_batch`].
+pub fn send_batch(transport: &mut dyn MmcTransport, commands: &[MmcCommand], debug: bool) -> Result<Vec<MmcResponse>, SdmonError> {
+    let res = transport.send_batch(commands);
+    if debug {
+        match &res {
+            Ok(responses) => {
+                for (command, response) in commands.iter().zip(responses) {
+                    debug_dump(command, &Ok(response.clone()));
+                }
+            }
+            Err(err) => {
+                dbg!(commands);
+                println!("Batch failed: {}", err);
+            }
+        }
+    }
+    return res;
+}
+
+/// Enables a mode with the CMD56 write `write_arg` and reads the block of
+/// `read_arg` in the same batch, so no other command gets in between.
+/// Returns the block and the R1 status of the read.
+pub fn cmd56_write_read(transport: &mut dyn MmcTransport, write_arg: u32, read_arg: u32, options: &CommandOptions, debug: bool) -> Result<(SDB1, CardStatus), SdmonError> {
+    let commands = [
+        MmcCommand::write(SD_GEN_CMD, write_arg, COMMAND_FLAGS_CMD56_WRITE, vec![0; SD_BLOCK_SIZE]).with_options(*options),
+        MmcCommand::read(SD_GEN_CMD, read_arg, COMMAND_FLAGS_CMD56_DATA_IN, SD_BLOCK_SIZE as u32).with_options(*options),
+    ];
+
+    let responses = with_retries(options, debug, || send_batch(transport, &commands, debug))?;
+    let mut responses = commands.iter().zip(responses).map(|(command, response)| check_status(command, response));
+    responses.next().transpose()?;
+    let read = responses.next().transpose()?.unwrap_or_default();
+    return Ok((SDB1::from_slice(&read.data), read.status()));
+}
+
+/// Reads the block of `cmd56_arg` and the R1 status of the read.
+pub fn cmd56_data_in(transport: &mut dyn MmcTransport, cmd56_arg: u32, options: &CommandOptions, debug: bool) -> Result<(SDB1, CardStatus), SdmonError> {
+    let command = MmcCommand::read(SD_GEN_CMD, cmd56_arg, COMMAND_FLAGS_CMD56_DATA_IN, SD_BLOCK_SIZE as u32).with_options(*options);
+
+    let res = with_retries(options, debug, || {
+        let res = transport.send(&command);
+        if debug {
+            debug_dump(&command, &res);
+        }
+        res
+    });
+
+    let response = check_status(&command, res?)?;
+    return Ok((SDB1::from_slice(&response.data), response.status()));
+}
+
+pub fn cmd56_write(transport: &mut dyn MmcTransport, cmd56_arg: u32, options: &CommandOptions, debug: bool) -> Result<(), SdmonError> {
+    let command = MmcCommand::write(SD_GEN_CMD, cmd56_arg, COMMAND_FLAGS_CMD56_WRITE, vec![0; SD_BLOCK_SIZE]).with_options(*options);
+
+    let res = with_retries(options, debug, || {
+        let res = transport.send(&command);
+        if debug {
+            debug_dump(&command, &res);
+        }
+        res
+    });
+
+    return check_status(&command, res?).map(|_| ());
+}
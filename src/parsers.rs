@@ -1,47 +1,240 @@
 use super::mmc_ioc_cmd::SDBlock;
 use super::mmc_ioc_cmd::Cmd56;
-use std::str;
+use super::layout::{Endian, EnumMap, FieldSpec, Format, Layout, Role, Source};
+use super::report::HealthReport;
 
 pub trait SDParser {
     fn check_signature(&self, _command:Cmd56, _block: &SDBlock) -> bool {
         return false;
     }
 
-    fn dump_data(&self, _block: &SDBlock) {
+    /// Field layout of `block`, see [`crate::layout`].
+    fn layout(&self, block: &SDBlock) -> &'static Layout;
 
+    fn parse(&self, block: &SDBlock) -> HealthReport {
+        return self.layout(block).parse(block);
     }
-}
-
-fn nb16(val1: u8, val2: u8) -> u16 {
-    return ((val1 as u16) << 8) | (val2 as u16);
-}
 
-fn nb32(val1: u8, val2: u8, val3: u8, val4:u8) -> u32 {
-    return ((val1 as u32) << 24) | 
-           ((val2 as u32) << 16) | 
-           ((val3 as u32) << 8)  | 
-           (val4 as u32);
-}
-
-fn nb64(val1: u8, val2: u8, val3: u8, val4:u8, val5: u8, val6: u8, val7: u8, val8:u8) -> u64 {
-    return ((val1 as u64) << 56) | 
-           ((val2 as u64) << 48) | 
-           ((val3 as u64) << 40) | 
-           ((val4 as u64) << 32) |
-           ((val5 as u64) << 24) | 
-           ((val6 as u64) << 16) | 
-           ((val7 as u64) << 8)  | 
-           (val8 as u64);
+    fn dump_data(&self, block: &SDBlock) {
+        print!("{}", self.parse(block));
+    }
 }
 
-fn nword_to_u32(block: &SDBlock, offset: usize) -> u32 {
-    return nb32(block[offset+3], block[offset+2], block[offset+1], block[offset]);
+static BUS_WIDTH: EnumMap = EnumMap {
+    values: &[(0x00, "1 bit"), (0x10, "4 bits")],
+    unknown: "Unknown",
+};
+
+static SPEED_CLASS: EnumMap = EnumMap {
+    values: &[(0x00, "Class 0"), (0x01, "Class 2"), (0x02, "Class 4"), (0x03, "Class 6"), (0x04, "Class 10")],
+    unknown: "Unknown",
+};
+
+static UHS_SPEED_GRADE: EnumMap = EnumMap {
+    values: &[(0x00, "Less than 10MB/s"), (0x01, "10MB/s and higher"), (0x03, "30MB/s and higher")],
+    unknown: "Unknown",
+};
+
+static SWISSBIT_SPEED_MODE: EnumMap = EnumMap {
+    values: &[
+        (0x00, "Default speed"),
+        (0x01, "High speed"),
+        (0x10, "SDR12 speed"),
+        (0x11, "SDR25 speed"),
+        (0x12, "SDR50 speed"),
+        (0x14, "DDR50 speed"),
+        (0x18, "SDR104 speed"),
+    ],
+    unknown: "unknown",
+};
+
+const fn enumerated(name: &'static str, offset: usize, map: &'static EnumMap) -> FieldSpec {
+    return FieldSpec::bytes(name, offset, 1, Format::Enum(map));
 }
 
-fn nword_to_u64(block: &SDBlock, offset: usize) -> u64 {
-    return nb64(block[offset+7], block[offset+6],block[offset+5],block[offset+4], 
-                block[offset+3], block[offset+2], block[offset+1], block[offset]);
-}
+pub static LONGSYS: Layout = Layout {
+    vendor: "Longsys",
+    card_type: "Longsys",
+    fields: &[
+        FieldSpec::le("SMARTVersions",      4, 4),
+        FieldSpec::le("sizeOfDevSMART",    12, 4),
+        FieldSpec::le("originalBadBlock",  16, 4).role(Role::FactoryBadBlocks),
+        FieldSpec::le("increaseBadBlock",  20, 4).role(Role::GrownBadBlocks),
+        FieldSpec::le("writeAllSectNum",   24, 8).unit("sectors"),
+        FieldSpec::le("replaceBlockLeft",  32, 4).role(Role::SpareBlocks),
+        FieldSpec::bytes("degreOfWear",    36, 4, Format::Scaled(Endian::Little, 1000.0)).unit("cycles"),
+        FieldSpec::le("sectorTotal",       40, 4),
+        FieldSpec::le("remainLifeTime",    44, 4).unit("%").role(Role::RemainingLife),
+        FieldSpec::bytes("remainWrGBNum",  48, 4, Format::Scaled(Endian::Little, 1024.0)).unit("TB"),
+        FieldSpec::le("lifeTimeTotal",     52, 4).unit("cycles"),
+        FieldSpec::bytes("phyWrGBNum",     56, 4, Format::Scaled(Endian::Little, 1024.0)).unit("TB"),
+    ],
+};
+
+/*
+1. SanDisk Industrial, compared to the data manual, adds 26L-24H, data name: power-on times
+2. SanDisk Industrial, compared to the data manual, adds 405-424, 20 Bytes, data name: product code, ASCII format
+3. SanDisk Industrial, compared to the data manual, adds 426-431, 6 Bytes, data name: product serial number, HEX format
+ */
+static SANDISK_FIELDS: &[FieldSpec] = &[
+    FieldSpec::bytes("manufactureYYMMDD", 2, 6, Format::Utf8),
+    // Counts up from 0 and exceeds 100 past the rated endurance.
+    FieldSpec::u8("healthStatusPercentUsed", 8).role(Role::LifeUsed),
+    FieldSpec::u8("featureRevision", 11),
+    FieldSpec::u8("generationIdentifier", 14),
+    FieldSpec::bytes("productString", 49, 32, Format::Utf8),
+    FieldSpec::be("power-on times", 25, 2).role(Role::PowerCycles),
+    FieldSpec::bytes("Tag", 405, 27, Format::PrintableAscii),
+];
+
+pub static SANDISK: Layout = Layout { vendor: "Sandisk", card_type: "Sandisk", fields: SANDISK_FIELDS };
+
+pub static WESTERN_DIGITAL: Layout = Layout { vendor: "Western Digital", card_type: "Western Digital", fields: SANDISK_FIELDS };
+
+pub static MICRON: Layout = Layout {
+    vendor: "Micron",
+    card_type: "Micron",
+    fields: &[
+        FieldSpec::u8("Percentange step utilization", 7),
+        FieldSpec::u8("TLC area utilization", 8),
+        FieldSpec::u8("SLC area utilization", 9),
+    ],
+};
+
+pub static SWISSBIT: Layout = Layout {
+    vendor: "Swissbit",
+    card_type: "Swissbit Micron",
+    fields: &[
+        FieldSpec::bytes("fwVersion", 32, 16, Format::List),
+        FieldSpec::be("User area rated cycles",              48, 4),
+        FieldSpec::be("User area max cycle cnt",             52, 4).role(Role::EraseMax),
+        FieldSpec::be("User area total cycle cnt",           56, 4).role(Role::EraseTotal),
+        FieldSpec::be("User area average cycle cnt",         60, 4).role(Role::EraseAvg),
+        FieldSpec::be("System area max cycle cnt",           68, 4),
+        FieldSpec::be("System area total cycle cnt",         72, 4),
+        FieldSpec::be("System area average cycle cnt",       76, 4),
+        FieldSpec::u8("Remaining Lifetime Percent",          80).unit("%").role(Role::RemainingLife),
+        enumerated("Speed mode",                             86, &SWISSBIT_SPEED_MODE),
+        enumerated("Bus width",                              87, &BUS_WIDTH),
+        FieldSpec::be("User area spare blocks cnt",          88, 4).role(Role::SpareBlocks),
+        FieldSpec::be("System area spare blocks cnt",        92, 4),
+        FieldSpec::be("User area runtime bad blocks cnt",    96, 4).role(Role::GrownBadBlocks),
+        FieldSpec::be("System area runtime bad blocks cnt", 100, 4),
+        FieldSpec::be("User area refresh cnt",              104, 4),
+        FieldSpec::be("System area refresh cnt",            108, 4),
+        FieldSpec::be("Interface crc cnt",                  112, 4),
+        FieldSpec::be("Power cycle cnt",                    116, 4).role(Role::PowerCycles),
+    ],
+};
+
+pub static TRANSCEND: Layout = Layout {
+    vendor: "Transcend",
+    card_type: "Transcend",
+    fields: &[
+        FieldSpec::bytes("Signature", 0, 2, Format::Bytes),
+        FieldSpec::u8("Secured mode",               11),
+        enumerated("Bus width",                     16, &BUS_WIDTH),
+        enumerated("Speed mode",                    18, &SPEED_CLASS),
+        enumerated("UHS speed grade",               19, &UHS_SPEED_GRADE),
+        FieldSpec::u8("New bad blocks cnt",         26).role(Role::GrownBadBlocks),
+        FieldSpec::u8("Runtime spare blocks cnt",   27).role(Role::SpareBlocks),
+        FieldSpec::le("Abnormal power loss",        28, 4),
+        FieldSpec::le("Minimum erase cnt",          32, 4).role(Role::EraseMin),
+        // Big-endian unlike its neighbours, kept from the original parser
+        // until a datasheet or a real dump settles the byte order.
+        FieldSpec::be("Maximum erase cnt",          36, 4).role(Role::EraseMax),
+        FieldSpec::le("Average erase cnt",          44, 4).role(Role::EraseAvg),
+        FieldSpec::u8("Remaining card life",        70).unit("%").role(Role::RemainingLife),
+        FieldSpec::be("Total write CRC cnt",        72, 4),
+        FieldSpec::be("Power cycle cnt",            76, 2).role(Role::PowerCycles),
+        FieldSpec::bytes("NAND flash ID",           80, 6, Format::Bytes),
+        FieldSpec::bytes("IC",                      87, 8, Format::Ascii),
+        FieldSpec::bytes("fw version",             128, 6, Format::Ascii),
+    ],
+};
+
+pub static ADATA: Layout = Layout {
+    vendor: "ADATA",
+    card_type: "ADATA",
+    fields: &[
+        FieldSpec::bytes("Signature", 0, 2, Format::Bytes),
+        FieldSpec::be("Factory bad block cnt",           24, 2).role(Role::FactoryBadBlocks),
+        FieldSpec::u8("Grown bad block cnt",             26).role(Role::GrownBadBlocks),
+        FieldSpec::u8("Spare SLC block cnt",             27),
+        FieldSpec::be("Spare block cnt",                 30, 2).role(Role::SpareBlocks),
+        FieldSpec::be("Data area minimum erase cnt",     32, 4).role(Role::EraseMin),
+        FieldSpec::be("Data area maximum erase cnt",     36, 4).role(Role::EraseMax),
+        FieldSpec::be("Data area total erase cnt",       40, 4).role(Role::EraseTotal),
+        FieldSpec::be("Data area average erase cnt",     44, 4).role(Role::EraseAvg),
+        FieldSpec::be("System area minimum erase cnt",   48, 4),
+        FieldSpec::be("System area maximum erase cnt",   52, 4),
+        FieldSpec::be("System area total erase cnt",     56, 4),
+        FieldSpec::be("System area average erase cnt",   60, 4),
+        FieldSpec::be("Raw card capacity",               64, 4).unit("MB"),
+        FieldSpec::be("PE Cycle life",                   68, 2),
+        FieldSpec::u8("Remaining life",                  70).unit("%").role(Role::RemainingLife),
+        FieldSpec::be("Power cycle cnt",                 76, 4).role(Role::PowerCycles),
+        FieldSpec::bytes("Flash ID",                     80, 7, Format::Bytes),
+        FieldSpec::bytes("Controller",                   88, 6, Format::Ascii),
+        FieldSpec::be("TLC read reclaim",                96, 2),
+        FieldSpec::be("SLC read reclaim",                98, 2),
+        FieldSpec::be("Firmware block refresh",         100, 2),
+        FieldSpec::be("TLC read threshold",             104, 4),
+        FieldSpec::be("SLC read threshold",             108, 4),
+        FieldSpec::bytes("FW version",                  128, 6, Format::Ascii),
+        FieldSpec::be("TLC refresh cnt",                136, 4),
+        FieldSpec::be("SLC refresh cnt",                140, 4),
+    ],
+};
+
+pub static INNODISK: Layout = Layout {
+    vendor: "Innodisk",
+    card_type: "Innodisk",
+    fields: &[
+        FieldSpec::bytes("Signature", 0, 2, Format::Bytes),
+        enumerated("Bus width",                    16, &BUS_WIDTH),
+        enumerated("Speed mode",                   18, &SPEED_CLASS),
+        enumerated("UHS speed grade",              19, &UHS_SPEED_GRADE),
+        FieldSpec::u8("Total spare blocks cnt",    24).role(Role::SpareBlocks),
+        FieldSpec::u8("Factory bad blocks cnt",    25).role(Role::FactoryBadBlocks),
+        FieldSpec::u8("Runtime bad blocks cnt",    26).role(Role::GrownBadBlocks),
+        FieldSpec::u8("Spare utilization rate",    27).unit("%"),
+        FieldSpec::be("SPOR failure cnt",          28, 4),
+        FieldSpec::le("Minimum erase cnt",         32, 4).role(Role::EraseMin),
+        FieldSpec::le("Maximum erase cnt",         36, 4).role(Role::EraseMax),
+        FieldSpec::le("Total erase cnt",           40, 4).role(Role::EraseTotal),
+        FieldSpec::le("Average erase cnt",         44, 4).role(Role::EraseAvg),
+        FieldSpec::bytes("FW version",             53, 7, Format::Ascii),
+    ],
+};
+
+pub static SMART_DATA: Layout = Layout {
+    vendor: "Generic",
+    card_type: "Generic Smart-capable SD",
+    fields: &[
+        FieldSpec::bytes("flashId",                 0, 9, Format::Bytes),
+        FieldSpec::bytes("icVersion",               9, 2, Format::Bytes),
+        FieldSpec::bytes("fwVersion",              11, 2, Format::List),
+        FieldSpec::u8("ceNumber",                  14),
+        FieldSpec::le("spareBlockCount",           16, 2).role(Role::SpareBlocks),
+        FieldSpec::bytes("initialBadBlockCount",   32, 31, Format::Sum).role(Role::FactoryBadBlocks),
+        FieldSpec::bytes("goodBlockRatePercent",   64, 2, Format::Scaled(Endian::Big, 100.0)).unit("%"),
+        FieldSpec::be("totalEraseCount",           80, 4).role(Role::EraseTotal),
+        FieldSpec::bytes("enduranceRemainLifePercent", 96, 2, Format::Scaled(Endian::Big, 100.0)).unit("%").role(Role::RemainingLife),
+        // The 32 bit counters are split into two halves.
+        FieldSpec::new("avgEraseCount", Source::Scattered(&[104, 105, 98, 99]), Format::Uint(Endian::Big)).role(Role::EraseAvg),
+        FieldSpec::new("minEraseCount", Source::Scattered(&[106, 107, 100, 101]), Format::Uint(Endian::Big)).role(Role::EraseMin),
+        FieldSpec::new("maxEraseCount", Source::Scattered(&[108, 109, 102, 103]), Format::Uint(Endian::Big)).role(Role::EraseMax),
+        FieldSpec::be("powerUpCount",             112, 4).role(Role::PowerCycles),
+        FieldSpec::be("abnormalPowerOffCount",    128, 2),
+        FieldSpec::be("totalRefreshCount",        160, 2),
+        FieldSpec::bytes("productMarker",         176, 8, Format::Bytes),
+        FieldSpec::bytes("laterBadBlockCount",    184, 31, Format::Sum).role(Role::GrownBadBlocks),
+    ],
+};
+
+/// All vendor specific layouts, for documentation and tooling.
+pub static LAYOUTS: &[&Layout] = &[&LONGSYS, &SANDISK, &WESTERN_DIGITAL, &MICRON, &SWISSBIT, &TRANSCEND, &ADATA, &INNODISK, &SMART_DATA];
 
 pub struct LongsysSDParser;
 pub struct SandiskSDParser;
@@ -57,20 +250,8 @@ impl SDParser for LongsysSDParser {
         return block[0] == 0x70 && block[1] == 0x58;
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        println!("Card type: Longsys");
-        println!("SMARTVersions: {}",                   nword_to_u32(block, 4));
-        println!("sizeOfDevSMART: {}",                  nword_to_u32(block, 12));
-        println!("originalBadBlock: {}",                nword_to_u32(block, 16));
-        println!("increaseBadBlock: {}",                nword_to_u32(block, 20));
-        println!("writeAllSectNum: {} Sector(512Byte)", nword_to_u64(block, 24));
-        println!("replaceBlockLeft: {}",                nword_to_u32(block, 32));
-        println!("degreOfWear: {} Cycle",               f64::from(nword_to_u32(block, 36))/1000.0);
-        println!("sectorTotal: {}",                     nword_to_u32(block, 40));
-        println!("remainLifeTime: {}%",                 nword_to_u32(block, 44));
-        println!("remainWrGBNum: {}TB",                 f64::from(nword_to_u32(block, 48))/1024.0);
-        println!("lifeTimeTotal: {} Cycle",             nword_to_u32(block, 52));
-        println!("phyWrGBNum: {}TB",                    f64::from(nword_to_u32(block, 56))/1024.0);
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &LONGSYS;
     }
 }
 
@@ -79,38 +260,11 @@ impl SDParser for SandiskSDParser {
         return block[0] == 0x44 && (block[1] == 0x53 || block[1] == 0x57);
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        let manufacture_yymmdd = str::from_utf8(&block[2..2+6]).unwrap();
-        let product_string = str::from_utf8(&block[49..49+32]).unwrap();
-        const TAG_SIZE: usize = 431 - 405 + 1;
-        let mut tag_bytes: [u8; TAG_SIZE] = [0; TAG_SIZE];
-        tag_bytes.clone_from_slice(&block[405..432]);
-        for i in 0..TAG_SIZE-1 {
-            if tag_bytes[i] < 0x20 || tag_bytes[i] > 0x7F
-            {
-                tag_bytes[i] = b'_';
-            }
-        }
-        let tag_string = str::from_utf8(&tag_bytes).unwrap();
-        
+    fn layout(&self, block: &SDBlock) -> &'static Layout {
         if block[1] == 0x57 {
-            println!("Card type: Western Digital");
-        } else {
-            println!("Card type: Sandisk");
+            return &WESTERN_DIGITAL;
         }
-        
-        println!("manufactureYYMMDD: {}", manufacture_yymmdd);
-        println!("healthStatusPercentUsed: {}", block[8]);
-        println!("featureRevision: {}", block[11]);
-        println!("generationIdentifier: {}", block[14]);
-        println!("productString: {}", product_string);
-        println!("power-on times: {}", nb32(0, 0, block[25], block[26]));
-        println!("Tag: {}", tag_string);
-        /*
-1. SanDisk Industrial, compared to the data manual, adds 26L-24H, data name: power-on times
-2. SanDisk Industrial, compared to the data manual, adds 405-424, 20 Bytes, data name: product code, ASCII format
-3. SanDisk Industrial, compared to the data manual, adds 426-431, 6 Bytes, data name: product serial number, HEX format
-         */
+        return &SANDISK;
     }
 }
 
@@ -119,11 +273,18 @@ impl SDParser for MicronSDParser {
         return block[0] == 0x4d && block[1] == 0x45;
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        println!("Card type: Micron");
-        println!("Percentange step utilization: {}", block[7]);
-        println!("TLC area utilization: {}", block[8]);
-        println!("SLC area utilization: {}", block[9]);
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &MICRON;
+    }
+
+    fn parse(&self, block: &SDBlock) -> HealthReport {
+        let mut report = MICRON.parse(block);
+
+        // The utilizations count steps of block[7] percent each.
+        if block[7] != 0 {
+            report.set_remaining_life(100.0 - f32::from(block[7]) * f32::from(block[8]));
+        }
+        return report;
     }
 }
 
@@ -132,44 +293,9 @@ impl SDParser for SwissbitSDParser {
         return block[0] == 0x53 && block[1] == 0x77;
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        println!("Card type: Swissbit Micron");
-
-        println!("fwVersion: [{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}]", block[32], block[33], block[34], block[35], block[36], block[37], block[38], block[39], block[40], block[41], block[42], block[43], block[44], block[45], block[46], block[47]); // show char
-        println!("User area rated cycles: {}", nb32(block[48], block[49], block[50], block[51])); 
-        println!("User area max cycle cnt: {}", nb32(block[52], block[53], block[54], block[55]));
-        println!("User area total cycle cnt: {}", nb32(block[56], block[57], block[58], block[59]));
-        println!("User area average cycle cnt: {}", nb32(block[60], block[61], block[62], block[63]));
-        println!("System area max cycle cnt: {}", nb32(block[68], block[69], block[70], block[71]));
-        println!("System area total cycle cnt: {}", nb32(block[72], block[73], block[74], block[75]));
-        println!("System area average cycle cnt: {}", nb32(block[76], block[77], block[78], block[79]));
-        println!("Remaining Lifetime Percent: {}%", block[80]);
-        match block[86]
-        {
-            0x00 => println!("Speed mode: Default speed"),
-            0x01 => println!("Speed mode: High speed"),
-            0x10 => println!("Speed mode: SDR12 speed"),
-            0x11 => println!("Speed mode: SDR25 speed"),
-            0x12 => println!("Speed mode: SDR50 speed"),
-            0x14 => println!("Speed mode: DDR50 speed"),
-            0x18 => println!("Speed mode: SDR104 speed"),
-            _ => println!("Speed mode: unknown ({})", block[86]),
-        }
-        match block[87]
-        {
-            0x00 => println!("Bus width: 1 bit"),
-            0x10 => println!("Bus width: 4 bits"),
-            _ => println!("Bus width: unknown ({})", block[87]),
-        }
-        println!("User area spare blocks cnt: {}", nb32(block[88], block[89], block[90], block[91]));
-        println!("System area spare blocks cnt: {}", nb32(block[92], block[93], block[94], block[95]));
-        println!("User area runtime bad blocks cnt: {}", nb32(block[96], block[97], block[98], block[99]));
-        println!("System area runtime bad blocks cnt: {}", nb32(block[100], block[101], block[102], block[103]));
-        println!("User area refresh cnt: {}", nb32(block[104], block[105],block[106], block[107]));
-        println!("System area refresh cnt: {}", nb32(block[108], block[109],block[110], block[111]));
-        println!("Interface crc cnt: {}", nb32(block[112], block[113],block[114], block[115]));
-        println!("Power cycle cnt: {}", nb32(block[116], block[117], block[118], block[119]));
-    } 
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &SWISSBIT;
+    }
 }
 
 impl SDParser for TranscendSDParser {
@@ -177,51 +303,8 @@ impl SDParser for TranscendSDParser {
         return block[0] == 0x54 && block[1] == 0x72;
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        println!("Signature: {:02X?} {:02X?}", block[0], block[1]);
-        println!("Transcend:true");
-        println!("Secured mode: {:02X?}", block[11]);
-        match block[16]
-        {
-            0x00 => println!("Bus width: 1 bit"),
-            0x10 => println!("Bus width: 4 bits"),
-            _ => println!("Bus width: Unknown ({})", block[16])
-        }
-
-        match block[18]
-        {
-            0x00 => println!("Speed mode: Class 0"),
-            0x01 => println!("Speed mode: Class 2"),
-            0x02 => println!("Speed mode: Class 4"),
-            0x03 => println!("Speed mode: Class 6"),
-            0x04 => println!("Speed mode: Class 10"),
-            _ => println!("Speed mode: Unknown ({})", block[18])
-        }
-        match block[19]
-        {
-            0x00 => println!("UHS speed grade: Less than 10MB/s"),
-            0x01 => println!("UHS speed grade: 10MB/s and higher"),
-            0x03 => println!("UHS speed grade: 30MB/s and higher"),
-            _ => println!("UHS speed grade: Unknown ({})", block[19])
-        }
-        println!("New bad blocks cnt: {:02X?}", block[26]);
-        println!("Runtime spare blocks cnt: {:02X?}", block[27]);
-        println!("Abnormal power loss: {}", nb32(block[31], block[30], block[29], block[28]));
-        println!("Minimum erase cnt: {}", nb32(block[35], block[34], block[33], block[32]));
-        println!("Maximum erase cnt: {}", nb32(block[36], block[37], block[38], block[39]));
-        println!("Average erase cnt: {}", nb32(block[47], block[46], block[45], block[44]));
-    
-        println!("Remaining card life: {}%", block[70]);
-        println!("Total write CRC cnt: {}", nb32(block[72], block[73], block[74], block[75]));
-        println!("Power cycle cnt: {}", nb32(0, 0, block[76], block[77]));
-    
-        println!("NAND flash ID: {:02X?} {:02X?} {:02X?} {:02X?} {:02X?} {:02X?}", block[80], block[81], block[82], block[83], block[84], block[85]);
-        println!("IC: {}{}{}{}{}{}{}{}", 
-            block[87] as char, block[88] as char, block[89] as char, block[90] as char, 
-            block[91] as char, block[92] as char, block[93] as char, block[94] as char);
-        println!("fw version: {}{}{}{}{}{}", 
-            block[128] as char, block[129] as char, block[130] as char, block[131] as char, 
-            block[132] as char, block[133] as char);
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &TRANSCEND;
     }
 }
 
@@ -230,40 +313,8 @@ impl SDParser for ADataSDParser {
         return block[0] == 0x09 && block[1] == 0x41;
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        println!("Signature: {:02X?} {:02X?}", block[0], block[1]);
-        println!("Adata:true");
-        println!("Factory bad block cnt: {}", nb16(block[24], block[25]));
-        println!("Grown bad block cnt: {}", block[26]);
-        println!("Spare SLC block cnt: {}", block[27]);
-        println!("Spare block cnt: {}",nb16(block[30] ,block[31]));
-        println!("Data area minimum erase cnt: {}", nb32(block[32], block[33], block[34], block[35]));
-        println!("Data area maximum erase cnt: {}", nb32(block[36], block[37], block[38], block[39]));
-        println!("Data area total erase cnt: {}", nb32(block[40], block[41], block[42], block[43]));
-        println!("Data area average erase cnt: {}", nb32(block[44], block[45], block[46], block[47]));
-        println!("System area minimum erase cnt: {}", nb32(block[48], block[49], block[50], block[51]));
-        println!("System area maximum erase cnt: {}", nb32(block[52], block[53], block[54], block[55]));
-        println!("System area total erase count: {}", nb32(block[56], block[57], block[58], block[59]));
-        println!("System area average erase cnt: {}", nb32(block[60], block[61], block[62], block[63]));
-        println!("Raw card capacity: {} MB", nb32(block[64], block[65], block[66], block[67]));
-        println!("PE Cycle life: {}", nb16(block[68], block[69]));
-        println!("Remaining life: {}%", block[70]);
-        println!("Power cucle cnt: {}", nb32(block[76], block[77], block[78], block[79]));
-        println!("Flash ID: {:02X?} {:02X?} {:02X?} {:02X?} {:02X?} {:02X?} {:02X?}", block[80], block[81], block[82], block[83], block[84], block[85], block[86]);
-        println!("Controller: {}{}{}{}{}{}", 
-            block[88] as char, block[89] as char, block[90] as char, block[91] as char, 
-            block[92] as char, block[93] as char);
-        println!("TLC read reclaim: {}", nb16(block[96], block[97]));
-        println!("SLC read reclaim: {}", nb16(block[98], block[99]));
-        println!("Firmware block refresh: {}", nb16(block[100], block[101]));
-        println!("TLC read threshold: {}", nb32(block[104], block[105], block[106], block[107]));
-        println!("SLC read threshold: {}", nb32(block[108], block[109], block[110], block[111]));
-        println!("FW version: {}{}{}{}{}{}", 
-            block[128] as char, block[129] as char, block[130] as char, block[131] as char, 
-            block[132] as char, block[133] as char);
-        println!("TLC refresh cnt: {}", nb32(block[136], block[137], block[138], block[139]));
-        println!("SLC refresh cnt: {}", nb32(block[140], block[141], block[143], block[144]));
-
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &ADATA;
     }
 }
 
@@ -272,46 +323,8 @@ impl SDParser for InnodiskSDParser {
         return command == Cmd56::LongsysM9H && block[0] == 0x4c && block[1] == 0x58;
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        println!("Signature: {:02X?} {:02X?}", block[0], block[1]);
-        println!("Innodisk:true");
-        match block[16]
-        {
-            0x00 => println!("Bus width: 1 bit"),
-            0x10 => println!("Bus width: 4 bits"),
-            _ => println!("Bus width: Unknown ({})", block[16])
-        }
-
-        match block[18]
-        {
-            0x00 => println!("Speed mode: Class 0"),
-            0x01 => println!("Speed mode: Class 2"),
-            0x02 => println!("Speed mode: Class 4"),
-            0x03 => println!("Speed mode: Class 6"),
-            0x04 => println!("Speed mode: Class 10"),
-            _ => println!("Speed mode: Unknown ({})", block[18])
-        }
-        match block[19]
-        {
-            0x00 => println!("UHS speed grade: Less than 10MB/s"),
-            0x01 => println!("UHS speed grade: 10MB/s and higher"),
-            0x03 => println!("UHS speed grade: 30MB/s and higher"),
-            _ => println!("UHS speed grade: Unknown ({})", block[19])
-        }
-	
-        println!("Total spare blocks cnt: {}", block[24]);
-	    println!("Factory bad blocks cnt: {}", block[25]);
-	    println!("Runtime bad blocks cnt: {}", block[26]);
-	    println!("Spare utilization rate: {}%", block[27]);
-	    println!("SPOR failure cnt: {}", nb32(block[28], block[29], block[30], block[31]));
-	    println!("Minimum erase cnt: {}", nb32(block[35], block[34], block[33], block[32]));
-	    println!("Maximum erase cnt: {}", nb32(block[39], block[38], block[37], block[36]));
-	    println!("Total erase cnt: {}", nb32(block[43], block[42], block[41], block[40]));
-	    println!("Average erase cnt: {}", nb32(block[47], block[46], block[45], block[44]));
-	    println!("FW version: {}{}{}{}{}{}{}", 
-            block[53] as char, block[54] as char, block[55] as char, block[56] as char, 
-            block[57] as char, block[58] as char, block[59] as char);
-
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &INNODISK;
     }
 }
 
@@ -320,50 +333,20 @@ impl SDParser for SmartDataSDParser {
         return (block[0] != 0x70 || block[1] != 0x58) && (block[0] != 0x44 || (block[1] != 0x53 || block[1] != 0x57));
     }
 
-    fn dump_data(&self, block: &SDBlock) {
-        let mut initial_bad_block_count: u16 = 0;
-        let mut later_bad_block_count: u16 = 0;
-
-        for i in 32..63 {
-            initial_bad_block_count = initial_bad_block_count + block[i] as u16;
-        }
-
-        for i in 184..215 {
-            later_bad_block_count = later_bad_block_count + block[i] as u16;
-        }
-
-        println!("Card type: Generic Smart-capable SD");
-        println!("flashId: [{:02X?},{:02X?},{:02X?},{:02X?},{:02X?},{:02X?},{:02X?},{:02X?},{:02X?}]",
-            block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7], block[8]);
-        println!("icVersion: [{:02X?},{:02X?}]", block[9], block[10]);
-        println!("fwVersion: [{},{}]", block[11], block[12]); // show in decimal
-        println!("ceNumber: {:02X?}", block[14]);
-        println!("spareBlockCount: {}", nb16(block[17], block[16]));
-        println!("initialBadBlockCount: {}", initial_bad_block_count);
-        println!("goodBlockRatePercent: {}", nb16(block[64], block[65]) as f32 / 100.0);
-        println!("totalEraseCount: {}", nb32(block[80], block[81], block[82], block[83]));
-        println!("enduranceRemainLifePercent: {}", nb16(block[97], block[97]) as f32 / 100.0);
-        println!("avgEraseCount: {}", nb32(block[104], block[105], block[98], block[99]));
-        println!("minEraseCount: {}", nb32(block[106], block[107], block[100], block[101]));
-        println!("maxEraseCount: {}", nb32(block[108], block[109], block[102], block[103]));
-        println!("powerUpCount: {}", nb32(block[112], block[113], block[114], block[115]));
-        println!("abnormalPowerOffCount: {}", nb16(block[128], block[129]));
-        println!("totalRefreshCount: {}", nb16(block[160], block[161]));
-        println!("productMarker: [{:02X?} {:02X?} {:02X?} {:02X?} {:02X?} {:02X?} {:02X?} {:02X?}]",
-            block[176], block[177], block[178], block[179], block[180], block[181], block[182], block[183]);
-        println!("laterBadBlockCount: {}", later_bad_block_count);
-
+    fn layout(&self, _block: &SDBlock) -> &'static Layout {
+        return &SMART_DATA;
     }
 }
 
 pub fn get_parsers() -> Vec<Box<dyn SDParser>> {
     vec![
-        Box::new(LongsysSDParser{}), 
-        Box::new(SandiskSDParser{}), 
-        Box::new(MicronSDParser{}), 
+        Box::new(LongsysSDParser{}),
+        Box::new(SandiskSDParser{}),
+        Box::new(MicronSDParser{}),
         Box::new(SwissbitSDParser{}),
         Box::new(TranscendSDParser{}),
-        Box::new(ADataSDParser{})]
+        Box::new(ADataSDParser{}),
+        Box::new(InnodiskSDParser{})]
 }
 
 pub fn get_smartdata_parser() -> Box<dyn SDParser> {
@@ -0,0 +1,148 @@
+//! Which CMD56 arguments are sent to an SD card.
+//!
+//! Without configuration the CID manufacturer ID picks the commands, see
+//! [`sd_strategy`]. `[[probe]]` rules of the configuration file override this
+//! per manufacturer ID, OEM ID and product name:
+//!
+//! ```toml
+//! [[probe]]
+//! mid = 0x74
+//! product = "USD00"
+//! commands = [0x110005F9]
+//! never = [0x53420001]
+//! postsleep-min-us = 1000
+//! cmd-timeout-ms = 500
+//! ```
+//!
+//! The first rule whose keys all match applies, a rule without keys matches
+//! every card. `commands` lists the vendor arguments in the order they are
+//! sent and replaces the built-in choice. Arguments in `never` are not sent
+//! in any case; listing the SMART enable `0x10` or read `0x21` there disables
+//! the generic SMART sequence.
+
+use serde::Deserialize;
+
+use crate::manufacturers::{sd_command_options, sd_strategy, ProbeStrategy, MMC_COMMAND_OPTIONS};
+use crate::mmc_ioc_cmd::{Cmd56, CMDS56};
+use crate::registers::Cid;
+use crate::transport::CommandOptions;
+use crate::{CMD56_SMART_ENABLE, CMD56_SMART_READ};
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProbeRule {
+    /// CID manufacturer ID.
+    pub mid: Option<u8>,
+    /// CID OEM ID as printed, e.g. `"JE"`.
+    pub oem: Option<String>,
+    /// CID product name.
+    pub product: Option<String>,
+    /// Vendor arguments to send, in this order.
+    pub commands: Option<Vec<u32>>,
+    /// Arguments never to send.
+    pub never: Vec<u32>,
+    pub postsleep_min_us: Option<u32>,
+    /// Defaults to `postsleep_min_us`.
+    pub postsleep_max_us: Option<u32>,
+    pub cmd_timeout_ms: Option<u32>,
+}
+
+impl ProbeRule {
+    /// Whether the rule applies to the card with `cid`; rules with keys never
+    /// apply to cards without CID.
+    pub fn matches(&self, cid: Option<&Cid>) -> bool {
+        let Some(cid) = cid else {
+            return self.mid.is_none() && self.oem.is_none() && self.product.is_none();
+        };
+        return self.mid.is_none_or(|mid| mid == cid.manufacturer_id)
+            && self.oem.as_ref().is_none_or(|oem| oem == cid.oem_id.trim())
+            && self.product.as_ref().is_none_or(|product| product == cid.product_name.trim());
+    }
+
+    /// Timings of the rule, zero where it leaves them out.
+    pub fn options(&self) -> CommandOptions {
+        let min = self.postsleep_min_us.unwrap_or(0);
+        let max = self.postsleep_max_us.or(self.postsleep_min_us).unwrap_or(0);
+        return CommandOptions::new().post_sleep_us(min, max).cmd_timeout_ms(self.cmd_timeout_ms.unwrap_or(0));
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for arg in self.commands.iter().flatten() {
+            Cmd56::try_from(*arg).map_err(|arg| format!("unknown CMD56 argument 0x{:08X} in commands", arg))?;
+        }
+        if let (Some(min), Some(max)) = (self.postsleep_min_us, self.postsleep_max_us) {
+            if max < min {
+                return Err("postsleep-max-us is less than postsleep-min-us".to_string());
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Commands sent to one SD card.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbePolicy {
+    /// Vendor commands in the order they are tried.
+    pub commands: Vec<Cmd56>,
+    /// Whether the generic SMART sequence is tried last.
+    pub smart: bool,
+    pub options: CommandOptions,
+}
+
+impl ProbePolicy {
+    pub fn new(commands: &[Cmd56]) -> Self {
+        return ProbePolicy { commands: commands.to_vec(), smart: true, options: CommandOptions::default() };
+    }
+
+    pub fn options(self, options: CommandOptions) -> Self {
+        return ProbePolicy { options, ..self };
+    }
+
+    /// Built-in policy for manufacturer ID `mid`.
+    pub fn builtin(mid: Option<u8>) -> Self {
+        let policy = match sd_strategy(mid) {
+            ProbeStrategy::Vendor(commands) => ProbePolicy::new(commands),
+            ProbeStrategy::GenericSmart => ProbePolicy::new(&[]),
+            ProbeStrategy::Sweep => ProbePolicy::new(&CMDS56),
+        };
+        return policy.options(sd_command_options(mid));
+    }
+
+    /// Built-in policy of the card with `cid` changed by the first matching
+    /// rule; timings the rule leaves out keep the manufacturer default.
+    pub fn resolve(cid: Option<&Cid>, rules: &[ProbeRule]) -> Self {
+        let mut policy = ProbePolicy::builtin(cid.map(|cid| cid.manufacturer_id));
+        let Some(rule) = rules.iter().find(|rule| rule.matches(cid)) else {
+            return policy;
+        };
+
+        if let Some(commands) = &rule.commands {
+            policy.commands = commands.iter().filter_map(|arg| Cmd56::try_from(*arg).ok()).collect();
+        }
+        policy.commands.retain(|cmd| !rule.never.contains(&(*cmd as u32)));
+        policy.smart = !rule.never.contains(&CMD56_SMART_ENABLE) && !rule.never.contains(&CMD56_SMART_READ);
+
+        policy.options = policy.options.merge(&rule.options());
+        return policy;
+    }
+
+    /// [`ProbePolicy::resolve`], but trying every argument of [`CMDS56`]; the
+    /// `never` list of the matching rule still applies.
+    pub fn resolve_sweep(cid: Option<&Cid>, rules: &[ProbeRule]) -> Self {
+        let mut policy = ProbePolicy::resolve(cid, rules);
+        let never = rules.iter().find(|rule| rule.matches(cid)).map(|rule| rule.never.as_slice()).unwrap_or_default();
+        policy.commands = CMDS56.iter().copied().filter(|cmd| !never.contains(&(*cmd as u32))).collect();
+        return policy;
+    }
+}
+
+/// Command options of an eMMC device with `cid`: the built-in default changed
+/// by the timings of the first matching rule. eMMC devices have no command
+/// list, so `commands` and `never` do not apply.
+pub fn resolve_mmc_options(cid: Option<&Cid>, rules: &[ProbeRule]) -> CommandOptions {
+    let options = MMC_COMMAND_OPTIONS;
+    return match rules.iter().find(|rule| rule.matches(cid)) {
+        Some(rule) => options.merge(&rule.options()),
+        None => options,
+    };
+}
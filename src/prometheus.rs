@@ -0,0 +1,184 @@
+//! node_exporter textfile collector output.
+//!
+//! Every metric carries the `device`, `vendor` and `serial` labels; the metric
+//! names do not depend on the vendor, the per vendor mapping happens in the
+//! parsers which fill the normalized members of [`HealthReport`]. In addition
+//! every numeric field of the report is exported as `sdmon_field` with its
+//! name and unit as labels.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+
+use crate::report::{FieldValue, HealthReport, HealthStatus};
+
+/// Health data of one device as exported to Prometheus. `report` is `None`
+/// when the device could not be read.
+pub struct DeviceSample<'a> {
+    pub device: &'a str,
+    pub serial: &'a str,
+    pub report: Option<&'a HealthReport>,
+}
+
+/// Optional extra label of a sample, e.g. `("kind", "grown")`.
+type ExtraLabel = Option<(&'static str, &'static str)>;
+
+/// One value of a metric, `None` when the vendor does not report it.
+type MetricValue = (ExtraLabel, Option<f64>);
+
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    /// `gauge`, or `counter` for metrics named `_total`.
+    kind: &'static str,
+    values: fn(&HealthReport) -> Vec<MetricValue>,
+}
+
+fn opt_f64(value: Option<u64>) -> Option<f64> {
+    return value.map(|v| v as f64);
+}
+
+fn health_status_value(status: HealthStatus) -> f64 {
+    match status {
+        HealthStatus::Good => 0.0,
+        HealthStatus::Warning => 1.0,
+        HealthStatus::Critical => 2.0,
+        HealthStatus::Unknown => 3.0,
+    }
+}
+
+const METRICS: &[Metric] = &[
+    Metric {
+        name: "sdmon_remaining_life_percent",
+        help: "Remaining card life in percent as reported by the card.",
+        kind: "gauge",
+        values: |r| vec![(None, r.remaining_life_percent.map(f64::from))],
+    },
+    Metric {
+        name: "sdmon_health_status",
+        help: "Health classification: 0 good, 1 warning, 2 critical, 3 unknown.",
+        kind: "gauge",
+        values: |r| vec![(None, Some(health_status_value(r.status)))],
+    },
+    Metric {
+        name: "sdmon_power_cycles_total",
+        help: "Number of power cycles the card has seen.",
+        kind: "counter",
+        values: |r| vec![(None, opt_f64(r.power_cycles))],
+    },
+    Metric {
+        name: "sdmon_spare_blocks",
+        help: "Number of spare blocks left.",
+        kind: "gauge",
+        values: |r| vec![(None, opt_f64(r.spare_blocks))],
+    },
+    Metric {
+        name: "sdmon_bad_blocks",
+        help: "Number of bad blocks by kind (factory or grown).",
+        kind: "gauge",
+        values: |r| vec![
+            (Some(("kind", "factory")), opt_f64(r.bad_blocks.factory)),
+            (Some(("kind", "grown")), opt_f64(r.bad_blocks.grown)),
+        ],
+    },
+    Metric {
+        name: "sdmon_erase_count",
+        help: "Block erase counts by statistic (min, max, avg, total).",
+        kind: "gauge",
+        values: |r| vec![
+            (Some(("stat", "min")), opt_f64(r.erase_count.min)),
+            (Some(("stat", "max")), opt_f64(r.erase_count.max)),
+            (Some(("stat", "avg")), opt_f64(r.erase_count.avg)),
+            (Some(("stat", "total")), opt_f64(r.erase_count.total)),
+        ],
+    },
+];
+
+fn escape_label(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+}
+
+fn labels(sample: &DeviceSample, vendor: &str, extra: ExtraLabel) -> String {
+    let mut labels = format!(
+        "device=\"{}\",vendor=\"{}\",serial=\"{}\"",
+        escape_label(sample.device), escape_label(vendor), escape_label(sample.serial));
+    if let Some((name, value)) = extra {
+        _ = write!(labels, ",{}=\"{}\"", name, escape_label(value));
+    }
+    return labels;
+}
+
+/// Renders the samples in the Prometheus text exposition format.
+pub fn render(samples: &[DeviceSample]) -> String {
+    let mut out = String::new();
+
+    _ = writeln!(out, "# HELP sdmon_probe_success Whether the health data of the device could be read.");
+    _ = writeln!(out, "# TYPE sdmon_probe_success gauge");
+    for sample in samples {
+        let vendor = sample.report.map(|r| r.vendor.as_str()).unwrap_or("");
+        _ = writeln!(out, "sdmon_probe_success{{{}}} {}", labels(sample, vendor, None), sample.report.is_some() as u8);
+    }
+
+    for metric in METRICS {
+        let mut lines = String::new();
+        for sample in samples {
+            let Some(report) = sample.report else { continue };
+            for (extra, value) in (metric.values)(report) {
+                if let Some(value) = value {
+                    _ = writeln!(lines, "{}{{{}}} {}", metric.name, labels(sample, &report.vendor, extra), value);
+                }
+            }
+        }
+        if !lines.is_empty() {
+            _ = writeln!(out, "# HELP {} {}", metric.name, metric.help);
+            _ = writeln!(out, "# TYPE {} {}", metric.name, metric.kind);
+            out.push_str(&lines);
+        }
+    }
+
+    let mut lines = String::new();
+    for sample in samples {
+        let Some(report) = sample.report else { continue };
+        for field in &report.fields {
+            let value = match field.value {
+                FieldValue::Uint(value) => value as f64,
+                FieldValue::Float(value) => value,
+                _ => continue,
+            };
+            _ = writeln!(lines, "sdmon_field{{{},field=\"{}\",unit=\"{}\"}} {}", labels(sample, &report.vendor, None),
+                escape_label(&field.name), escape_label(field.unit.as_deref().unwrap_or("")), value);
+        }
+    }
+    if !lines.is_empty() {
+        _ = writeln!(out, "# HELP sdmon_field Numeric vendor field of the health block, by field name and unit.");
+        _ = writeln!(out, "# TYPE sdmon_field gauge");
+        out.push_str(&lines);
+    }
+
+    return out;
+}
+
+/// Writes `contents` to `path` atomically: the data goes to a temporary file
+/// in the same directory which is then renamed over `path`, so the collector
+/// never sees a partially written file.
+pub fn write_textfile(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp_path = Path::new(&tmp_name);
+
+    let result = fs::File::create(tmp_path).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+
+    match result.and_then(|_| fs::rename(tmp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            _ = fs::remove_file(tmp_path);
+            Err(err)
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Card identification and capability registers (CID, CSD, SCR, SSR).
+//!
+//! The registers are read from sysfs (`/sys/block/mmcblkN/device/{cid,csd,scr,ssr}`).
+//! SCR and SSR can also be fetched with ACMD51 and ACMD13 through
+//! MMC_IOC_CMD. CID and CSD have no such fallback: CMD10 and CMD9 are only
+//! accepted in stand-by state, while the block driver keeps the card selected.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::Path;
+
+use crate::discovery::CardKind;
+use crate::error::SdmonError;
+use crate::manufacturers::{lookup_mmc, lookup_sd};
+use crate::mmc_ioc_cmd::COMMAND_FLAGS_DATA_IN;
+use crate::transport::{MmcCommand, MmcTransport};
+
+const SD_APP_SD_STATUS: u32 = 13;
+const SD_APP_SEND_SCR: u32 = 51;
+
+/// Extracts bits `msb..=lsb` of a big-endian register, numbering bits like
+/// the specifications do (bit 0 is the LSB of the last byte).
+pub fn bits(register: &[u8], msb: usize, lsb: usize) -> u64 {
+    let width = register.len() * 8;
+    let mut value: u64 = 0;
+    for bit in (lsb..=msb).rev() {
+        let index = width - 1 - bit;
+        let set = (register[index / 8] >> (7 - index % 8)) & 1;
+        value = (value << 1) | u64::from(set);
+    }
+    return value;
+}
+
+fn ascii(value: u64, len: usize) -> String {
+    return (0..len).rev()
+        .map(|i| ((value >> (i * 8)) & 0xFF) as u8)
+        .filter(|b| *b != 0)
+        .map(|b| b as char)
+        .collect();
+}
+
+fn decode_hex(text: &str, len: usize) -> Option<Vec<u8>> {
+    let bytes = crate::report::hex::decode(text.trim())?;
+    return if bytes.len() == len { Some(bytes) } else { None };
+}
+
+/// Card identification register.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cid {
+    pub manufacturer_id: u8,
+    /// Manufacturer name when the ID is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    pub oem_id: String,
+    pub product_name: String,
+    /// Product revision as `major.minor`.
+    pub revision: String,
+    pub serial_number: u32,
+    /// Manufacturing date as `YYYY-MM`.
+    pub manufacturing_date: String,
+}
+
+impl Cid {
+    /// Decodes the 16 byte CID of an SD card.
+    pub fn decode_sd(cid: &[u8]) -> Self {
+        let manufacturer_id = bits(cid, 127, 120) as u8;
+        return Cid {
+            manufacturer_id,
+            manufacturer: lookup_sd(manufacturer_id).map(|manufacturer| manufacturer.name.to_string()),
+            oem_id: ascii(bits(cid, 119, 104), 2),
+            product_name: ascii(bits(cid, 103, 64), 5),
+            revision: format!("{}.{}", bits(cid, 63, 60), bits(cid, 59, 56)),
+            serial_number: bits(cid, 55, 24) as u32,
+            manufacturing_date: format!("{:04}-{:02}", 2000 + bits(cid, 19, 12), bits(cid, 11, 8)),
+        };
+    }
+
+    /// Decodes the 16 byte CID of an (e)MMC device. The year is counted from
+    /// 1997, see JESD84 for devices which count from 2013.
+    pub fn decode_mmc(cid: &[u8]) -> Self {
+        let manufacturer_id = bits(cid, 127, 120) as u8;
+        return Cid {
+            manufacturer_id,
+            manufacturer: lookup_mmc(manufacturer_id).map(|manufacturer| manufacturer.name.to_string()),
+            oem_id: format!("0x{:02X}", bits(cid, 111, 104)),
+            product_name: ascii(bits(cid, 103, 56), 6),
+            revision: format!("{}.{}", bits(cid, 55, 52), bits(cid, 51, 48)),
+            serial_number: bits(cid, 47, 16) as u32,
+            manufacturing_date: format!("{:04}-{:02}", 1997 + bits(cid, 11, 8), bits(cid, 15, 12)),
+        };
+    }
+}
+
+/// Card specific data register, reduced to the capacity.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Csd {
+    pub structure: u8,
+    pub capacity_bytes: u64,
+}
+
+impl Csd {
+    pub fn decode_sd(csd: &[u8]) -> Self {
+        let structure = bits(csd, 127, 126) as u8;
+        let capacity_bytes = match structure {
+            // CSD version 1.0, standard capacity
+            0 => (bits(csd, 73, 62) + 1) << (bits(csd, 49, 47) + 2 + bits(csd, 83, 80)),
+            // CSD version 2.0 (SDHC/SDXC) and 3.0 (SDUC)
+            1 => (bits(csd, 69, 48) + 1) * 512 * 1024,
+            _ => (bits(csd, 75, 48) + 1) * 512 * 1024,
+        };
+        return Csd { structure, capacity_bytes };
+    }
+
+    /// Decodes the CSD of an (e)MMC device. Devices above 2GB report the real
+    /// capacity only in EXT_CSD SEC_COUNT.
+    pub fn decode_mmc(csd: &[u8]) -> Self {
+        let capacity_bytes = (bits(csd, 73, 62) + 1) << (bits(csd, 49, 47) + 2 + bits(csd, 83, 80));
+        return Csd { structure: bits(csd, 127, 126) as u8, capacity_bytes };
+    }
+}
+
+/// SD configuration register.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scr {
+    /// Physical layer specification version, e.g. `3.0x`.
+    pub sd_spec: String,
+    /// Bus widths supported, bit 0 for 1 bit and bit 2 for 4 bits.
+    pub bus_widths: u8,
+    pub cmd_support: u8,
+}
+
+impl Scr {
+    pub fn decode(scr: &[u8]) -> Self {
+        let sd_spec = match (bits(scr, 59, 56), bits(scr, 47, 47), bits(scr, 42, 42), bits(scr, 41, 38)) {
+            (0, _, _, _) => "1.0".to_string(),
+            (1, _, _, _) => "1.1".to_string(),
+            (2, 0, _, _) => "2.0".to_string(),
+            (2, 1, 0, 0) => "3.0x".to_string(),
+            (2, 1, 1, 0) => "4.xx".to_string(),
+            (2, 1, _, specx) => format!("{}.xx", specx + 4),
+            (spec, _, _, _) => format!("unknown ({})", spec),
+        };
+        return Scr { sd_spec, bus_widths: bits(scr, 51, 48) as u8, cmd_support: bits(scr, 35, 32) as u8 };
+    }
+}
+
+/// SD status register, reduced to the performance classes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ssr {
+    /// Speed class 0, 2, 4, 6 or 10.
+    pub speed_class: Option<u8>,
+    /// UHS speed grade U1 or U3, 0 for none.
+    pub uhs_speed_grade: u8,
+    /// Video speed class V6 .. V90, 0 for none.
+    pub video_speed_class: u8,
+    /// Application performance class A1 or A2, 0 for none.
+    pub app_perf_class: u8,
+}
+
+impl Ssr {
+    pub fn decode(ssr: &[u8]) -> Self {
+        let speed_class = match bits(ssr, 447, 440) {
+            0 => Some(0),
+            1 => Some(2),
+            2 => Some(4),
+            3 => Some(6),
+            4 => Some(10),
+            _ => None,
+        };
+        return Ssr {
+            speed_class,
+            uhs_speed_grade: bits(ssr, 399, 396) as u8,
+            video_speed_class: bits(ssr, 391, 384) as u8,
+            app_perf_class: bits(ssr, 339, 336) as u8,
+        };
+    }
+}
+
+/// The registers that could be read from a card.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CardRegisters {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cid: Option<Cid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csd: Option<Csd>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scr: Option<Scr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssr: Option<Ssr>,
+}
+
+impl CardRegisters {
+    /// CID serial number as hex, the label used to identify a card.
+    pub fn serial(&self) -> Option<String> {
+        return self.cid.as_ref().map(|cid| format!("0x{:08x}", cid.serial_number));
+    }
+}
+
+impl Display for CardRegisters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        if let Some(cid) = &self.cid {
+            match &cid.manufacturer {
+                Some(name) => writeln!(f, "Manufacturer ID: 0x{:02X} ({})", cid.manufacturer_id, name)?,
+                None => writeln!(f, "Manufacturer ID: 0x{:02X}", cid.manufacturer_id)?,
+            }
+            writeln!(f, "OEM ID: {}", cid.oem_id)?;
+            writeln!(f, "Product name: {}", cid.product_name)?;
+            writeln!(f, "Product revision: {}", cid.revision)?;
+            writeln!(f, "Serial number: 0x{:08x}", cid.serial_number)?;
+            writeln!(f, "Manufacturing date: {}", cid.manufacturing_date)?;
+        }
+        if let Some(csd) = &self.csd {
+            writeln!(f, "Capacity: {} bytes", csd.capacity_bytes)?;
+        }
+        if let Some(scr) = &self.scr {
+            writeln!(f, "SD specification: {}", scr.sd_spec)?;
+        }
+        if let Some(ssr) = &self.ssr {
+            if let Some(speed_class) = ssr.speed_class {
+                writeln!(f, "Speed class: Class {}", speed_class)?;
+            }
+            if ssr.uhs_speed_grade != 0 {
+                writeln!(f, "UHS speed grade: U{}", ssr.uhs_speed_grade)?;
+            }
+            if ssr.video_speed_class != 0 {
+                writeln!(f, "Video speed class: V{}", ssr.video_speed_class)?;
+            }
+            if ssr.app_perf_class != 0 {
+                writeln!(f, "Application performance class: A{}", ssr.app_perf_class)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+fn read_sysfs(card_dir: &Path, name: &str, len: usize) -> Option<Vec<u8>> {
+    return fs::read_to_string(card_dir.join(name)).ok().and_then(|text| decode_hex(&text, len));
+}
+
+/// Reads the registers exported by sysfs in `card_dir`, e.g.
+/// `/sys/block/mmcblk0/device`.
+pub fn read_sysfs_registers(card_dir: &Path, kind: CardKind) -> CardRegisters {
+    let mmc = kind == CardKind::Mmc;
+    return CardRegisters {
+        cid: read_sysfs(card_dir, "cid", 16).map(|cid| if mmc { Cid::decode_mmc(&cid) } else { Cid::decode_sd(&cid) }),
+        csd: read_sysfs(card_dir, "csd", 16).map(|csd| if mmc { Csd::decode_mmc(&csd) } else { Csd::decode_sd(&csd) }),
+        scr: read_sysfs(card_dir, "scr", 8).map(|scr| Scr::decode(&scr)),
+        ssr: read_sysfs(card_dir, "ssr", 64).map(|ssr| Ssr::decode(&ssr)),
+    };
+}
+
+fn send_acmd(transport: &mut dyn MmcTransport, opcode: u32, len: u32) -> Result<Vec<u8>, SdmonError> {
+    let mut command = MmcCommand::read(opcode, 0, COMMAND_FLAGS_DATA_IN, len);
+    command.is_acmd = true;
+    return Ok(transport.send(&command)?.data);
+}
+
+/// Reads the SCR with ACMD51 (SEND_SCR).
+pub fn read_scr(transport: &mut dyn MmcTransport) -> Result<Scr, SdmonError> {
+    return send_acmd(transport, SD_APP_SEND_SCR, 8).map(|scr| Scr::decode(&scr));
+}
+
+/// Reads the SSR with ACMD13 (SD_STATUS).
+pub fn read_ssr(transport: &mut dyn MmcTransport) -> Result<Ssr, SdmonError> {
+    return send_acmd(transport, SD_APP_SD_STATUS, 64).map(|ssr| Ssr::decode(&ssr));
+}
+
+/// Reads the registers from sysfs and fetches a missing SCR or SSR of SD
+/// cards over `transport`.
+pub fn read_registers(card_dir: &Path, kind: CardKind, transport: &mut dyn MmcTransport) -> CardRegisters {
+    let mut registers = read_sysfs_registers(card_dir, kind);
+
+    if matches!(kind, CardKind::Sd | CardKind::SdCombo) {
+        if registers.scr.is_none() {
+            registers.scr = read_scr(transport).ok();
+        }
+        if registers.ssr.is_none() {
+            registers.ssr = read_ssr(transport).ok();
+        }
+    }
+
+    return registers;
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// Decoded value of a single health field.
+///
+/// Serialized as `"type": "uint", "value": 42` so that consumers do not have
+/// to guess the type of a value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum FieldValue {
+    Uint(u64),
+    Float(f64),
+    Text(String),
+    /// Raw bytes, rendered as hex.
+    Bytes(#[serde(with = "hex")] Vec<u8>),
+    /// Byte sequence rendered as decimal numbers (e.g. firmware versions).
+    List(Vec<u64>),
+}
+
+impl From<u8> for FieldValue {
+    fn from(value: u8) -> Self {
+        return FieldValue::Uint(value.into());
+    }
+}
+
+impl From<u16> for FieldValue {
+    fn from(value: u16) -> Self {
+        return FieldValue::Uint(value.into());
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        return FieldValue::Uint(value.into());
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(value: u64) -> Self {
+        return FieldValue::Uint(value);
+    }
+}
+
+impl From<f32> for FieldValue {
+    fn from(value: f32) -> Self {
+        return FieldValue::Float(value.into());
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        return FieldValue::Float(value);
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        return FieldValue::Text(value);
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        return FieldValue::Text(value.to_string());
+    }
+}
+
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            FieldValue::Uint(value) => write!(f, "{}", value),
+            FieldValue::Float(value) => write!(f, "{}", value),
+            FieldValue::Text(value) => write!(f, "{}", value),
+            FieldValue::Bytes(bytes) => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X?}", b)).collect();
+                write!(f, "{}", hex.join(" "))
+            }
+            FieldValue::List(values) => {
+                let items: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", items.join(","))
+            }
+        }
+    }
+}
+
+/// One named value decoded from a vendor health block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(flatten)]
+    pub value: FieldValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self.unit.as_deref() {
+            None => write!(f, "{}: {}", self.name, self.value),
+            Some("%") => write!(f, "{}: {}%", self.name, self.value),
+            Some(unit) => write!(f, "{}: {} {}", self.name, self.value, unit),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BadBlocks {
+    /// Bad blocks marked at the factory.
+    pub factory: Option<u64>,
+    /// Bad blocks which appeared during the card's lifetime.
+    pub grown: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EraseCount {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub avg: Option<u64>,
+    pub total: Option<u64>,
+}
+
+/// Coarse health classification, comparable across vendors.
+///
+/// Derived from the normalized remaining life: `Critical` at or below
+/// [`CRITICAL_LIFE_PERCENT`], `Warning` at or below [`WARNING_LIFE_PERCENT`],
+/// `Good` above, `Unknown` when the vendor reports no wear. eMMC devices are
+/// additionally classified by PRE_EOL_INFO (Warning, Urgent → Critical).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Good,
+    Warning,
+    Critical,
+    #[default]
+    Unknown,
+}
+
+pub const WARNING_LIFE_PERCENT: f32 = 30.0;
+pub const CRITICAL_LIFE_PERCENT: f32 = 10.0;
+
+impl HealthStatus {
+    pub fn from_remaining_life(percent: f32) -> Self {
+        if percent <= CRITICAL_LIFE_PERCENT {
+            return HealthStatus::Critical;
+        }
+        if percent <= WARNING_LIFE_PERCENT {
+            return HealthStatus::Warning;
+        }
+        return HealthStatus::Good;
+    }
+
+    fn severity(self) -> u8 {
+        match self {
+            HealthStatus::Unknown => 0,
+            HealthStatus::Good => 1,
+            HealthStatus::Warning => 2,
+            HealthStatus::Critical => 3,
+        }
+    }
+
+    /// The more severe of both, a known status wins over `Unknown`.
+    pub fn worst(self, other: HealthStatus) -> HealthStatus {
+        return if other.severity() > self.severity() { other } else { self };
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HealthStatus::Good => "Good",
+            HealthStatus::Warning => "Warning",
+            HealthStatus::Critical => "Critical",
+            HealthStatus::Unknown => "Unknown",
+        }
+    }
+}
+
+/// Health data of a card as decoded by an [`SDParser`](crate::parsers::SDParser).
+///
+/// `fields` holds everything the vendor block contains, the remaining members
+/// are the vendor independent subset filled in whenever the vendor reports it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub vendor: String,
+    pub card_type: String,
+    pub fields: Vec<Field>,
+    /// Remaining life in percent, 100 for a new card, whatever the vendor's
+    /// own scale is.
+    pub remaining_life_percent: Option<f32>,
+    #[serde(default)]
+    pub status: HealthStatus,
+    pub power_cycles: Option<u64>,
+    pub spare_blocks: Option<u64>,
+    pub bad_blocks: BadBlocks,
+    pub erase_count: EraseCount,
+}
+
+impl HealthReport {
+    pub fn new(vendor: &str, card_type: &str) -> Self {
+        return HealthReport { vendor: vendor.to_string(), card_type: card_type.to_string(), ..Default::default() };
+    }
+
+    /// Appends a field without unit.
+    pub fn add(&mut self, name: &str, value: impl Into<FieldValue>) {
+        self.fields.push(Field { name: name.to_string(), value: value.into(), unit: None });
+    }
+
+    /// Appends a field measured in `unit`.
+    pub fn add_with_unit(&mut self, name: &str, value: impl Into<FieldValue>, unit: &str) {
+        self.fields.push(Field { name: name.to_string(), value: value.into(), unit: Some(unit.to_string()) });
+    }
+
+    /// Sets the remaining life, clamped to 0..=100 %, and the status derived
+    /// from it.
+    pub fn set_remaining_life(&mut self, percent: f32) {
+        let percent = percent.clamp(0.0, 100.0);
+        self.remaining_life_percent = Some(percent);
+        self.status = HealthStatus::from_remaining_life(percent);
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        return self.fields.iter().find(|field| field.name == name);
+    }
+}
+
+/// Plain text rendering, one `name: value` line per field.
+impl Display for HealthReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        writeln!(f, "Card type: {}", self.card_type)?;
+        for field in &self.fields {
+            writeln!(f, "{}", field)?;
+        }
+        match self.remaining_life_percent {
+            Some(percent) => writeln!(f, "Health status: {} ({}% life remaining)", self.status.name(), percent),
+            None => writeln!(f, "Health status: {}", self.status.name()),
+        }
+    }
+}
+
+/// Uppercase hex string encoding of byte vectors, matching the dumps printed
+/// by `SDB1`.
+pub mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn encode(bytes: &[u8]) -> String {
+        return bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    }
+
+    pub fn decode(text: &str) -> Option<Vec<u8>> {
+        if !text.len().is_multiple_of(2) || !text.is_ascii() {
+            return None;
+        }
+        return (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+            .collect();
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&encode(bytes));
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        return decode(&text).ok_or_else(|| D::Error::custom("invalid hex string"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Built-in HTTP exporter.
+//!
+//! | Path | Content |
+//! | --- | --- |
+//! | `/metrics` | Prometheus text format, see [`prometheus::render`] |
+//! | `/api/devices` | JSON array with the `--json` document of every device |
+//! | `/api/devices/{name}` | JSON document of one device, e.g. `mmcblk0` |
+//!
+//! Devices are read at most once per minimum interval, requests in between
+//! are answered from the cache. Requests are handled one at a time, so two
+//! scrapes never talk to a card concurrently.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use crate::error::SdmonError;
+use crate::json::DeviceDocument;
+use crate::prometheus::{render, DeviceSample};
+use crate::Detection;
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:9901";
+
+const MAX_REQUEST_SIZE: usize = 8192;
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads the health of a device, [`read_health`](crate::read_health) outside
+/// of tests.
+pub type DeviceReader = Box<dyn FnMut(&str) -> Result<Detection, SdmonError> + Send>;
+
+struct CachedResult {
+    read_at: Instant,
+    result: Result<Detection, SdmonError>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn new(status: u16, content_type: &'static str, body: String) -> Self {
+        return HttpResponse { status, content_type, body };
+    }
+
+    fn json(status: u16, value: &serde_json::Value) -> Self {
+        return HttpResponse::new(status, "application/json", value.to_string() + "\n");
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Error",
+        }
+    }
+}
+
+/// Device name used in URLs, the file name of the device path.
+pub fn device_name(device: &str) -> &str {
+    return Path::new(device).file_name().and_then(|name| name.to_str()).unwrap_or(device);
+}
+
+pub struct Exporter {
+    devices: Vec<String>,
+    min_interval: Duration,
+    reader: DeviceReader,
+    cache: HashMap<String, CachedResult>,
+}
+
+impl Exporter {
+    pub fn new(devices: Vec<String>, min_interval: Duration, reader: DeviceReader) -> Self {
+        return Exporter { devices, min_interval, reader, cache: HashMap::new() };
+    }
+
+    /// Re-reads `device` unless the cached result is younger than the
+    /// minimum interval.
+    fn refresh(&mut self, device: &str) {
+        let fresh = self.cache.get(device).is_some_and(|cached| cached.read_at.elapsed() < self.min_interval);
+        if !fresh {
+            let result = (self.reader)(device);
+            self.cache.insert(device.to_string(), CachedResult { read_at: Instant::now(), result });
+        }
+    }
+
+    fn document(&self, device: &str) -> serde_json::Value {
+        match &self.cache[device].result {
+            Ok(detection) => serde_json::to_value(DeviceDocument::new(device, detection)).expect("health report is always serializable"),
+            Err(err) => json!({ "device": device, "error": err.to_string() }),
+        }
+    }
+
+    fn metrics(&mut self) -> HttpResponse {
+        let devices = self.devices.clone();
+        for device in &devices {
+            self.refresh(device);
+        }
+
+        let serials: Vec<String> = devices.iter()
+            .map(|device| self.cache[device].result.as_ref().ok().and_then(|detection| detection.registers.serial()).unwrap_or_default())
+            .collect();
+        let samples: Vec<DeviceSample> = devices.iter().zip(&serials)
+            .map(|(device, serial)| DeviceSample {
+                device,
+                serial,
+                report: self.cache[device].result.as_ref().ok().map(|detection| &detection.report),
+            })
+            .collect();
+
+        return HttpResponse::new(200, "text/plain; version=0.0.4", render(&samples));
+    }
+
+    /// Answers a request for `path`.
+    pub fn handle(&mut self, method: &str, path: &str) -> HttpResponse {
+        if method != "GET" {
+            return HttpResponse::json(405, &json!({ "error": "only GET is supported" }));
+        }
+
+        let path = path.split('?').next().unwrap_or(path);
+        match path {
+            "/metrics" => self.metrics(),
+            "/api/devices" => {
+                let devices = self.devices.clone();
+                let documents: Vec<serde_json::Value> = devices.iter()
+                    .map(|device| {
+                        self.refresh(device);
+                        self.document(device)
+                    })
+                    .collect();
+                HttpResponse::json(200, &serde_json::Value::Array(documents))
+            }
+            _ => {
+                let name = path.strip_prefix("/api/devices/").unwrap_or("");
+                let Some(device) = self.devices.iter().find(|device| !name.is_empty() && device_name(device) == name).cloned() else {
+                    return HttpResponse::json(404, &json!({ "error": "not found" }));
+                };
+                self.refresh(&device);
+                HttpResponse::json(200, &self.document(&device))
+            }
+        }
+    }
+
+    /// Reads one request from `stream` and writes the response.
+    pub fn handle_connection(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+            let read = stream.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+        let response = match (parts.next(), parts.next()) {
+            (Some(method), Some(path)) => self.handle(method, path),
+            _ => HttpResponse::json(400, &json!({ "error": "malformed request" })),
+        };
+
+        write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status, response.reason(), response.content_type, response.body.len())?;
+        stream.write_all(response.body.as_bytes())?;
+        return stream.flush();
+    }
+
+    /// Serves requests from `listener`, forever.
+    pub fn serve(&mut self, listener: &TcpListener) {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|mut stream| self.handle_connection(&mut stream));
+            if let Err(err) = result {
+                eprintln!("HTTP client error: {}", err);
+            }
+        }
+    }
+}
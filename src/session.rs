@@ -0,0 +1,274 @@
+//! Recording of raw MMC command sessions and their replay.
+//!
+//! A session file is little-endian binary: the magic `SDMONSES`, a `u32`
+//! format version, the recorded device path as `u32` length plus UTF-8 bytes,
+//! a `u8` read-only flag (since version 2), a `u32` entry count and then per
+//! command
+//!
+//! | Field | Size |
+//! | --- | --- |
+//! | opcode, arg, flags | 3 × `u32` |
+//! | write flag, is_acmd | 2 × `u8` |
+//! | blksz, blocks | 2 × `u32` |
+//! | errno, 0 on success | `i32` |
+//! | response words | 4 × `u32` |
+//! | data length and data | `u32` + bytes |
+
+use nix::errno::Errno;
+use std::fs;
+use std::path::Path;
+
+use crate::error::SdmonError;
+use crate::Detection;
+use crate::manufacturers::SD_COMMAND_OPTIONS;
+use crate::mmc_ioc_cmd::{Cmd56, SD_GEN_CMD};
+use crate::policy::ProbePolicy;
+use crate::{probe_policy, CMD56_SMART_ENABLE, CMD56_SMART_READ};
+use crate::transport::{CommandOptions, MmcCommand, MmcResponse, MmcTransport, ReadOnlyTransport, ScriptedExchange, ScriptedTransport};
+
+const MAGIC: &[u8; 8] = b"SDMONSES";
+const VERSION: u32 = 2;
+
+/// One command of a session and what the card answered.
+#[derive(Clone, Debug)]
+pub struct SessionEntry {
+    pub command: MmcCommand,
+    pub result: Result<MmcResponse, Errno>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    pub device: String,
+    /// Commands changing the card were refused while recording, the replay
+    /// refuses them too.
+    pub read_only: bool,
+    pub entries: Vec<SessionEntry>,
+}
+
+impl Session {
+    pub fn new(device: &str) -> Self {
+        return Session { device: device.to_string(), read_only: false, entries: Vec::new() };
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        put_bytes(&mut out, self.device.as_bytes());
+        out.push(self.read_only as u8);
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+
+        for entry in &self.entries {
+            let command = &entry.command;
+            for value in [command.opcode, command.arg, command.flags] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            out.push(command.write as u8);
+            out.push(command.is_acmd as u8);
+            out.extend_from_slice(&command.blksz.to_le_bytes());
+            out.extend_from_slice(&command.blocks.to_le_bytes());
+
+            let (errno, response) = match &entry.result {
+                Ok(response) => (0, response.clone()),
+                Err(errno) => (*errno as i32, MmcResponse::default()),
+            };
+            out.extend_from_slice(&errno.to_le_bytes());
+            for word in response.response {
+                out.extend_from_slice(&word.to_le_bytes());
+            }
+            // Write commands keep their payload so the session shows what was sent.
+            let data = if command.write { &command.data } else { &response.data };
+            put_bytes(&mut out, data);
+        }
+
+        return out;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SdmonError> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SdmonError::Parse("not an sdmon session file".to_string()));
+        }
+        let version = reader.u32()?;
+        if version == 0 || version > VERSION {
+            return Err(SdmonError::Parse(format!("unsupported session version {}", version)));
+        }
+
+        let device = String::from_utf8_lossy(reader.bytes()?).to_string();
+        let mut session = Session::new(&device);
+        session.read_only = version >= 2 && reader.u8()? != 0;
+        let count = reader.u32()?;
+
+        for _ in 0..count {
+            let opcode = reader.u32()?;
+            let arg = reader.u32()?;
+            let flags = reader.u32()?;
+            let write = reader.u8()? != 0;
+            let is_acmd = reader.u8()? != 0;
+            let blksz = reader.u32()?;
+            let blocks = reader.u32()?;
+            let errno = reader.u32()? as i32;
+            let response = [reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?];
+            let data = reader.bytes()?.to_vec();
+
+            let command = MmcCommand {
+                opcode, arg, flags, write, is_acmd, blksz, blocks,
+                data: if write { data.clone() } else { Vec::new() },
+                options: CommandOptions::default(),
+            };
+            let result = if errno == 0 { Ok(MmcResponse { response, data }) } else { Err(Errno::from_i32(errno)) };
+            session.entries.push(SessionEntry { command, result });
+        }
+
+        return Ok(session);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SdmonError> {
+        return fs::write(path, self.to_bytes()).map_err(|err| SdmonError::io(path, err));
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SdmonError> {
+        let bytes = fs::read(path).map_err(|err| SdmonError::io(path, err))?;
+        return Session::from_bytes(&bytes);
+    }
+
+    /// Policy sending the recorded CMD56 arguments in the recorded order, so
+    /// the replay takes the path of the recording whatever policy that used.
+    pub fn policy(&self) -> ProbePolicy {
+        let mut commands = Vec::new();
+        let mut smart = false;
+        for command in self.entries.iter().map(|entry| &entry.command).filter(|command| command.opcode == SD_GEN_CMD) {
+            match Cmd56::try_from(command.arg) {
+                Ok(cmd) if !commands.contains(&cmd) => commands.push(cmd),
+                Ok(_) => {}
+                Err(arg) => smart |= arg == CMD56_SMART_ENABLE || arg == CMD56_SMART_READ,
+            }
+        }
+
+        let mut policy = ProbePolicy::new(&commands).options(CommandOptions::new().retries(SD_COMMAND_OPTIONS.retries));
+        policy.smart = smart;
+        return policy;
+    }
+
+    /// Probes the replay with [`Session::policy`], read-only when the
+    /// recording was.
+    pub fn probe(&self, debug: bool) -> Result<Detection, SdmonError> {
+        let mut replay = self.replay();
+        if self.read_only {
+            return probe_policy(&mut ReadOnlyTransport::new(&mut replay), &self.policy(), debug);
+        }
+        return probe_policy(&mut replay, &self.policy(), debug);
+    }
+
+    /// Transport answering the recorded commands in the recorded order.
+    pub fn replay(&self) -> ScriptedTransport {
+        let mut transport = ScriptedTransport::new();
+        for entry in &self.entries {
+            transport.push(ScriptedExchange {
+                opcode: entry.command.opcode,
+                arg: entry.command.arg,
+                result: entry.result.clone(),
+            });
+        }
+        return transport;
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SdmonError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(SdmonError::Parse("truncated session file".to_string()));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        return Ok(slice);
+    }
+
+    fn u8(&mut self) -> Result<u8, SdmonError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u32(&mut self) -> Result<u32, SdmonError> {
+        let bytes = self.take(4)?;
+        return Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SdmonError> {
+        let len = self.u32()? as usize;
+        return self.take(len);
+    }
+}
+
+/// Transport which forwards every command to `inner` and records it.
+pub struct RecordingTransport<'a> {
+    inner: &'a mut dyn MmcTransport,
+    session: Session,
+}
+
+impl<'a> RecordingTransport<'a> {
+    pub fn new(device: &str, inner: &'a mut dyn MmcTransport) -> Self {
+        return RecordingTransport { inner, session: Session::new(device) };
+    }
+
+    pub fn session(&self) -> &Session {
+        return &self.session;
+    }
+}
+
+impl MmcTransport for RecordingTransport<'_> {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError> {
+        let res = self.inner.send(command);
+
+        let result = match &res {
+            Ok(response) => Ok(response.clone()),
+            Err(SdmonError::Ioctl { errno, .. }) => Err(*errno),
+            Err(SdmonError::WriteRefused { .. }) => {
+                self.session.read_only = true;
+                return res;
+            }
+            Err(_) => return res,
+        };
+        self.session.entries.push(SessionEntry { command: command.clone(), result });
+
+        return res;
+    }
+
+    /// A failed batch is recorded as failing at the command named by the
+    /// error, the commands before it as answered without data, so the replay
+    /// takes the same path. A refused batch marks the session read-only.
+    fn send_batch(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+        let res = self.inner.send_batch(commands);
+
+        match &res {
+            Ok(responses) => {
+                for (command, response) in commands.iter().zip(responses) {
+                    self.session.entries.push(SessionEntry { command: command.clone(), result: Ok(response.clone()) });
+                }
+            }
+            Err(SdmonError::Ioctl { opcode, arg, errno }) => {
+                let failed = commands.iter().position(|command| command.opcode == *opcode && command.arg == *arg).unwrap_or(0);
+                for command in &commands[..failed] {
+                    self.session.entries.push(SessionEntry { command: command.clone(), result: Ok(MmcResponse::default()) });
+                }
+                if let Some(command) = commands.get(failed) {
+                    self.session.entries.push(SessionEntry { command: command.clone(), result: Err(*errno) });
+                }
+            }
+            Err(SdmonError::WriteRefused { .. }) => self.session.read_only = true,
+            Err(_) => {}
+        }
+
+        return res;
+    }
+}
@@ -0,0 +1,193 @@
+//! R1 card status, returned in the first response word of most SD and MMC
+//! commands.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub const OUT_OF_RANGE: u32 = 1 << 31;
+pub const ADDRESS_ERROR: u32 = 1 << 30;
+pub const BLOCK_LEN_ERROR: u32 = 1 << 29;
+pub const ERASE_SEQ_ERROR: u32 = 1 << 28;
+pub const ERASE_PARAM: u32 = 1 << 27;
+pub const WP_VIOLATION: u32 = 1 << 26;
+pub const CARD_IS_LOCKED: u32 = 1 << 25;
+pub const LOCK_UNLOCK_FAILED: u32 = 1 << 24;
+pub const COM_CRC_ERROR: u32 = 1 << 23;
+pub const ILLEGAL_COMMAND: u32 = 1 << 22;
+pub const CARD_ECC_FAILED: u32 = 1 << 21;
+pub const CC_ERROR: u32 = 1 << 20;
+pub const ERROR: u32 = 1 << 19;
+pub const CSD_OVERWRITE: u32 = 1 << 16;
+pub const WP_ERASE_SKIP: u32 = 1 << 15;
+pub const CARD_ECC_DISABLED: u32 = 1 << 14;
+pub const ERASE_RESET: u32 = 1 << 13;
+pub const READY_FOR_DATA: u32 = 1 << 8;
+pub const FX_EVENT: u32 = 1 << 6;
+pub const APP_CMD: u32 = 1 << 5;
+pub const AKE_SEQ_ERROR: u32 = 1 << 3;
+
+const CURRENT_STATE_SHIFT: u32 = 9;
+const CURRENT_STATE_MASK: u32 = 0xF;
+
+/// Flag bits and their names as in the SD specification.
+const FLAGS: &[(u32, &str)] = &[
+    (OUT_OF_RANGE, "OUT_OF_RANGE"),
+    (ADDRESS_ERROR, "ADDRESS_ERROR"),
+    (BLOCK_LEN_ERROR, "BLOCK_LEN_ERROR"),
+    (ERASE_SEQ_ERROR, "ERASE_SEQ_ERROR"),
+    (ERASE_PARAM, "ERASE_PARAM"),
+    (WP_VIOLATION, "WP_VIOLATION"),
+    (CARD_IS_LOCKED, "CARD_IS_LOCKED"),
+    (LOCK_UNLOCK_FAILED, "LOCK_UNLOCK_FAILED"),
+    (COM_CRC_ERROR, "COM_CRC_ERROR"),
+    (ILLEGAL_COMMAND, "ILLEGAL_COMMAND"),
+    (CARD_ECC_FAILED, "CARD_ECC_FAILED"),
+    (CC_ERROR, "CC_ERROR"),
+    (ERROR, "ERROR"),
+    (CSD_OVERWRITE, "CSD_OVERWRITE"),
+    (WP_ERASE_SKIP, "WP_ERASE_SKIP"),
+    (CARD_ECC_DISABLED, "CARD_ECC_DISABLED"),
+    (ERASE_RESET, "ERASE_RESET"),
+    (READY_FOR_DATA, "READY_FOR_DATA"),
+    (FX_EVENT, "FX_EVENT"),
+    (APP_CMD, "APP_CMD"),
+    (AKE_SEQ_ERROR, "AKE_SEQ_ERROR"),
+];
+
+/// Bits reporting that the command or the one before it went wrong.
+const ERROR_BITS: u32 = OUT_OF_RANGE | ADDRESS_ERROR | BLOCK_LEN_ERROR | ERASE_SEQ_ERROR | ERASE_PARAM
+    | WP_VIOLATION | LOCK_UNLOCK_FAILED | COM_CRC_ERROR | ILLEGAL_COMMAND | CARD_ECC_FAILED | CC_ERROR
+    | ERROR | CSD_OVERWRITE | AKE_SEQ_ERROR;
+
+/// CURRENT_STATE of the card when it received the command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardState {
+    Idle,
+    Ready,
+    Ident,
+    Standby,
+    Transfer,
+    Data,
+    Receive,
+    Program,
+    Disconnect,
+    Reserved(u8),
+}
+
+impl CardState {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits {
+            0 => CardState::Idle,
+            1 => CardState::Ready,
+            2 => CardState::Ident,
+            3 => CardState::Standby,
+            4 => CardState::Transfer,
+            5 => CardState::Data,
+            6 => CardState::Receive,
+            7 => CardState::Program,
+            8 => CardState::Disconnect,
+            _ => CardState::Reserved(bits),
+        }
+    }
+}
+
+impl Display for CardState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            CardState::Idle => write!(f, "idle"),
+            CardState::Ready => write!(f, "ready"),
+            CardState::Ident => write!(f, "ident"),
+            CardState::Standby => write!(f, "stby"),
+            CardState::Transfer => write!(f, "tran"),
+            CardState::Data => write!(f, "data"),
+            CardState::Receive => write!(f, "rcv"),
+            CardState::Program => write!(f, "prg"),
+            CardState::Disconnect => write!(f, "dis"),
+            CardState::Reserved(bits) => write!(f, "reserved ({})", bits),
+        }
+    }
+}
+
+/// R1 card status word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CardStatus(pub u32);
+
+impl CardStatus {
+    pub fn new(bits: u32) -> Self {
+        return CardStatus(bits);
+    }
+
+    pub fn bits(&self) -> u32 {
+        return self.0;
+    }
+
+    pub fn is_set(&self, flag: u32) -> bool {
+        return self.0 & flag != 0;
+    }
+
+    /// The card does not know the command or argument, for CMD56 the
+    /// vendor command is not supported.
+    pub fn illegal_command(&self) -> bool {
+        return self.is_set(ILLEGAL_COMMAND);
+    }
+
+    pub fn com_crc_error(&self) -> bool {
+        return self.is_set(COM_CRC_ERROR);
+    }
+
+    pub fn card_is_locked(&self) -> bool {
+        return self.is_set(CARD_IS_LOCKED);
+    }
+
+    /// General or unknown error.
+    pub fn error(&self) -> bool {
+        return self.is_set(ERROR);
+    }
+
+    /// The card expects an application command, i.e. CMD55 came before.
+    pub fn app_cmd(&self) -> bool {
+        return self.is_set(APP_CMD);
+    }
+
+    pub fn ready_for_data(&self) -> bool {
+        return self.is_set(READY_FOR_DATA);
+    }
+
+    pub fn current_state(&self) -> CardState {
+        return CardState::from_bits(((self.0 >> CURRENT_STATE_SHIFT) & CURRENT_STATE_MASK) as u8);
+    }
+
+    /// Whether any error bit is set.
+    pub fn has_error(&self) -> bool {
+        return self.is_set(ERROR_BITS);
+    }
+
+    /// Names of the set flag bits, most significant first.
+    pub fn flags(&self) -> Vec<&'static str> {
+        return FLAGS.iter().filter(|(bit, _)| self.is_set(*bit)).map(|(_, name)| *name).collect();
+    }
+}
+
+/// E.g. `0x00400900 tran ILLEGAL_COMMAND READY_FOR_DATA`.
+impl Display for CardStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "0x{:08X} {}", self.0, self.current_state())?;
+        for name in self.flags() {
+            write!(f, " {}", name)?;
+        }
+        return Ok(());
+    }
+}
+
+/// `{"raw": "0x00000900", "state": "tran", "flags": ["READY_FOR_DATA"]}`.
+impl Serialize for CardStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CardStatus", 3)?;
+        state.serialize_field("raw", &format!("0x{:08X}", self.0))?;
+        state.serialize_field("state", &self.current_state().to_string())?;
+        state.serialize_field("flags", &self.flags())?;
+        return state.end();
+    }
+}
@@ -0,0 +1,267 @@
+//! Abstraction over the MMC_IOC_CMD ioctl so the probing logic can run
+//! against a real card ([`IoctlTransport`](crate::mmc_ioc_cmd::IoctlTransport))
+//! or against canned responses ([`ScriptedTransport`]).
+
+use nix::errno::Errno;
+use std::collections::VecDeque;
+
+use crate::error::SdmonError;
+use crate::status::CardStatus;
+
+/// Timing of a command as passed to the kernel, zero leaves the kernel
+/// default, and how often it is repeated after a transient error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CommandOptions {
+    /// Sleep after the command, between the minimum and maximum.
+    pub postsleep_min_us: u32,
+    pub postsleep_max_us: u32,
+    /// Timeout of the data transfer.
+    pub data_timeout_ns: u32,
+    /// Timeout of the command itself, e.g. for the busy signal of R1b.
+    pub cmd_timeout_ms: u32,
+    /// Repetitions after ETIMEDOUT or EILSEQ, with doubling pauses.
+    pub retries: u32,
+}
+
+impl CommandOptions {
+    pub const fn new() -> Self {
+        return CommandOptions { postsleep_min_us: 0, postsleep_max_us: 0, data_timeout_ns: 0, cmd_timeout_ms: 0, retries: 0 };
+    }
+
+    pub const fn post_sleep_us(self, min: u32, max: u32) -> Self {
+        return CommandOptions { postsleep_min_us: min, postsleep_max_us: max, ..self };
+    }
+
+    pub const fn data_timeout_ns(self, data_timeout_ns: u32) -> Self {
+        return CommandOptions { data_timeout_ns, ..self };
+    }
+
+    pub const fn cmd_timeout_ms(self, cmd_timeout_ms: u32) -> Self {
+        return CommandOptions { cmd_timeout_ms, ..self };
+    }
+
+    pub const fn retries(self, retries: u32) -> Self {
+        return CommandOptions { retries, ..self };
+    }
+
+    /// `self` with every non-zero value of `other` replacing its own.
+    pub fn merge(self, other: &CommandOptions) -> Self {
+        let pick = |own: u32, other: u32| if other != 0 { other } else { own };
+        let (postsleep_min_us, postsleep_max_us) = if other.postsleep_min_us != 0 {
+            (other.postsleep_min_us, other.postsleep_max_us.max(other.postsleep_min_us))
+        } else {
+            (self.postsleep_min_us, self.postsleep_max_us)
+        };
+        return CommandOptions {
+            postsleep_min_us,
+            postsleep_max_us,
+            data_timeout_ns: pick(self.data_timeout_ns, other.data_timeout_ns),
+            cmd_timeout_ms: pick(self.cmd_timeout_ms, other.cmd_timeout_ms),
+            retries: pick(self.retries, other.retries),
+        };
+    }
+}
+
+/// A single MMC command as handed to the kernel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MmcCommand {
+    pub opcode: u32,
+    pub arg: u32,
+    pub flags: u32,
+    /// Data direction, `true` when `data` is sent to the card.
+    pub write: bool,
+    /// Send CMD55 (APP_CMD) first.
+    pub is_acmd: bool,
+    pub blksz: u32,
+    pub blocks: u32,
+    /// Payload of write commands, empty for reads.
+    pub data: Vec<u8>,
+    pub options: CommandOptions,
+}
+
+impl MmcCommand {
+    /// Command reading one block of `blksz` bytes from the card.
+    pub fn read(opcode: u32, arg: u32, flags: u32, blksz: u32) -> Self {
+        return MmcCommand { opcode, arg, flags, write: false, is_acmd: false, blksz, blocks: 1, data: Vec::new(), options: CommandOptions::default() };
+    }
+
+    /// Command sending `data` as one block to the card.
+    pub fn write(opcode: u32, arg: u32, flags: u32, data: Vec<u8>) -> Self {
+        return MmcCommand { opcode, arg, flags, write: true, is_acmd: false, blksz: data.len() as u32, blocks: 1, data, options: CommandOptions::default() };
+    }
+
+    /// Command without data transfer.
+    pub fn no_data(opcode: u32, arg: u32, flags: u32) -> Self {
+        return MmcCommand { opcode, arg, flags, write: false, is_acmd: false, blksz: 0, blocks: 0, data: Vec::new(), options: CommandOptions::default() };
+    }
+
+    pub fn with_options(self, options: CommandOptions) -> Self {
+        return MmcCommand { options, ..self };
+    }
+
+    /// Size of the data transfer in bytes.
+    pub fn data_len(&self) -> usize {
+        return (self.blksz * self.blocks) as usize;
+    }
+}
+
+/// What the card answered to an [`MmcCommand`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MmcResponse {
+    /// Response words as filled in by the host controller.
+    pub response: [u32; 4],
+    /// Data read from the card; for write commands the data that was sent.
+    pub data: Vec<u8>,
+}
+
+impl MmcResponse {
+    /// R1 card status in the first response word; meaningless for commands
+    /// with other response types.
+    pub fn status(&self) -> CardStatus {
+        return CardStatus::new(self.response[0]);
+    }
+}
+
+pub trait MmcTransport {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError>;
+
+    /// Sends `commands` as one batch which no other command can interleave
+    /// with, returning one response per command. The default sends them one
+    /// by one and stops at the first failure, see [`send_each`].
+    fn send_batch(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+        return send_each(self, commands);
+    }
+}
+
+/// Sends `commands` one by one, without atomicity; the fallback of
+/// [`MmcTransport::send_batch`].
+pub fn send_each<T: MmcTransport + ?Sized>(transport: &mut T, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+    return commands.iter().map(|command| transport.send(command)).collect();
+}
+
+/// Vendor commands without data which still switch the card into another
+/// mode: CMD62 enters the Samsung eMMC vendor mode, in which CMD17 returns
+/// the health report instead of user data.
+pub const MODE_SWITCHING_OPCODES: &[u32] = &[62];
+
+/// Transport which refuses every command sending data to the card or
+/// switching its mode with [`SdmonError::WriteRefused`] and forwards the
+/// others to `inner`.
+pub struct ReadOnlyTransport<'a> {
+    inner: &'a mut dyn MmcTransport,
+    refused: Vec<MmcCommand>,
+}
+
+impl<'a> ReadOnlyTransport<'a> {
+    pub fn new(inner: &'a mut dyn MmcTransport) -> Self {
+        return ReadOnlyTransport { inner, refused: Vec::new() };
+    }
+
+    /// Commands refused so far.
+    pub fn refused(&self) -> &[MmcCommand] {
+        return &self.refused;
+    }
+
+    fn refuse(&mut self, command: &MmcCommand) -> SdmonError {
+        self.refused.push(command.clone());
+        return SdmonError::WriteRefused { opcode: command.opcode, arg: command.arg };
+    }
+}
+
+fn changes_card(command: &MmcCommand) -> bool {
+    return command.write || MODE_SWITCHING_OPCODES.contains(&command.opcode);
+}
+
+impl MmcTransport for ReadOnlyTransport<'_> {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError> {
+        if changes_card(command) {
+            return Err(self.refuse(command));
+        }
+        return self.inner.send(command);
+    }
+
+    /// Refuses the whole batch when one of its commands changes the card.
+    fn send_batch(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+        if let Some(command) = commands.iter().find(|command| changes_card(command)) {
+            return Err(self.refuse(command));
+        }
+        return self.inner.send_batch(commands);
+    }
+}
+
+/// One expected command and the canned answer of a [`ScriptedTransport`].
+#[derive(Clone, Debug)]
+pub struct ScriptedExchange {
+    pub opcode: u32,
+    pub arg: u32,
+    pub result: Result<MmcResponse, Errno>,
+}
+
+/// In-memory transport which replays canned responses in order.
+///
+/// Every command must match the opcode and argument of the next exchange,
+/// otherwise `send` fails with [`SdmonError::Parse`]; this makes the tests
+/// check the command sequence as well.
+#[derive(Default)]
+pub struct ScriptedTransport {
+    exchanges: VecDeque<ScriptedExchange>,
+    sent: Vec<MmcCommand>,
+}
+
+impl ScriptedTransport {
+    pub fn new() -> Self {
+        return ScriptedTransport::default();
+    }
+
+    pub fn push(&mut self, exchange: ScriptedExchange) {
+        self.exchanges.push_back(exchange);
+    }
+
+    /// Answers the command with `data` and all-zero response words.
+    pub fn respond(mut self, opcode: u32, arg: u32, data: &[u8]) -> Self {
+        self.push(ScriptedExchange { opcode, arg, result: Ok(MmcResponse { response: [0; 4], data: data.to_vec() }) });
+        return self;
+    }
+
+    /// Answers the command with `data` and `status` as R1 card status.
+    pub fn respond_with_status(mut self, opcode: u32, arg: u32, data: &[u8], status: u32) -> Self {
+        self.push(ScriptedExchange { opcode, arg, result: Ok(MmcResponse { response: [status, 0, 0, 0], data: data.to_vec() }) });
+        return self;
+    }
+
+    /// Fails the command as if the ioctl returned `errno`.
+    pub fn fail(mut self, opcode: u32, arg: u32, errno: Errno) -> Self {
+        self.push(ScriptedExchange { opcode, arg, result: Err(errno) });
+        return self;
+    }
+
+    /// Commands sent so far.
+    pub fn sent(&self) -> &[MmcCommand] {
+        return &self.sent;
+    }
+
+    /// Number of exchanges not replayed yet.
+    pub fn remaining(&self) -> usize {
+        return self.exchanges.len();
+    }
+}
+
+impl MmcTransport for ScriptedTransport {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError> {
+        self.sent.push(command.clone());
+
+        let exchange = match self.exchanges.pop_front() {
+            Some(exchange) if exchange.opcode == command.opcode && exchange.arg == command.arg => exchange,
+            Some(exchange) => return Err(SdmonError::Parse(format!(
+                "expected CMD{} arg 0x{:08X}, got CMD{} arg 0x{:08X}",
+                exchange.opcode, exchange.arg, command.opcode, command.arg))),
+            None => return Err(SdmonError::Parse(format!(
+                "unexpected CMD{} arg 0x{:08X} after end of script", command.opcode, command.arg))),
+        };
+
+        let mut response = exchange.result
+            .map_err(|errno| SdmonError::Ioctl { opcode: command.opcode, arg: command.arg, errno })?;
+        response.data.resize(command.data_len(), 0);
+        return Ok(response);
+    }
+}
@@ -0,0 +1,192 @@
+//! Wear rate and end-of-life projection from a card's history.
+//!
+//! The remaining life of a sample is computed from the average erase count
+//! and the rated cycles where the vendor reports both (Swissbit `User area
+//! rated cycles`, ADATA `PE Cycle life`), which is finer grained than the
+//! whole percent most cards report, and taken from the normalized
+//! `remaining_life_percent` otherwise. Written data comes from Longsys
+//! `phyWrGBNum`. Rates are least squares fits over all samples.
+
+use serde::Serialize;
+
+use crate::history::HistoryRecord;
+use crate::report::{FieldValue, HealthReport};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Where the remaining life of the samples came from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifeSource {
+    /// Average erase count against the rated cycles.
+    RatedCycles,
+    /// `remaining_life_percent` as reported by the card.
+    Reported,
+}
+
+/// Why a trend has no end-of-life projection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoProjection {
+    /// Fewer than two samples at different times.
+    TooFewSamples,
+    /// No sample reports the remaining life or the erase count and rated cycles.
+    NoWearMetric,
+    /// The remaining life did not go down.
+    NoMeasurableWear,
+}
+
+impl std::fmt::Display for NoProjection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            NoProjection::TooFewSamples => write!(f, "fewer than two records for this serial"),
+            NoProjection::NoWearMetric => write!(f, "the card reports no wear metric"),
+            NoProjection::NoMeasurableWear => write!(f, "no measurable wear"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WearTrend {
+    pub samples: usize,
+    /// Unix timestamps of the first and last sample.
+    pub first: u64,
+    pub last: u64,
+    /// Remaining life of the last sample in percent.
+    pub life_percent: Option<f64>,
+    pub life_source: Option<LifeSource>,
+    /// Life lost per day in percentage points, positive while the card wears.
+    pub life_per_day: Option<f64>,
+    /// Data written to the flash per day.
+    pub written_tb_per_day: Option<f64>,
+    /// Remaining life the projection is made for.
+    pub eol_life_percent: f64,
+    /// Projected Unix timestamp at which `eol_life_percent` is reached, `None`
+    /// without measurable wear.
+    pub eol_timestamp: Option<u64>,
+}
+
+impl WearTrend {
+    /// Why `eol_timestamp` is `None`.
+    pub fn no_projection(&self) -> Option<NoProjection> {
+        if self.eol_timestamp.is_some() {
+            return None;
+        }
+        if self.life_source.is_none() {
+            return Some(NoProjection::NoWearMetric);
+        }
+        if self.life_per_day.is_none() {
+            return Some(NoProjection::TooFewSamples);
+        }
+        return Some(NoProjection::NoMeasurableWear);
+    }
+}
+
+fn uint_field(report: &HealthReport, name: &str) -> Option<u64> {
+    match report.field(name).map(|field| &field.value) {
+        Some(FieldValue::Uint(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+fn float_field(report: &HealthReport, name: &str) -> Option<f64> {
+    match report.field(name).map(|field| &field.value) {
+        Some(FieldValue::Float(value)) => Some(*value),
+        Some(FieldValue::Uint(value)) => Some(*value as f64),
+        _ => None,
+    }
+}
+
+/// Rated program/erase cycles of the card, when the vendor reports them.
+fn rated_cycles(report: &HealthReport) -> Option<u64> {
+    return uint_field(report, "User area rated cycles")
+        .or_else(|| uint_field(report, "PE Cycle life"))
+        .filter(|cycles| *cycles > 0);
+}
+
+/// Remaining life of one sample in percent and where it came from.
+pub fn sample_life(report: &HealthReport) -> Option<(f64, LifeSource)> {
+    if let (Some(rated), Some(avg)) = (rated_cycles(report), report.erase_count.avg) {
+        let life = 100.0 * (1.0 - avg as f64 / rated as f64);
+        return Some((life.max(0.0), LifeSource::RatedCycles));
+    }
+    return report.remaining_life_percent.map(|life| (f64::from(life), LifeSource::Reported));
+}
+
+/// Data written to the flash in TB, when the vendor reports it.
+pub fn sample_written_tb(report: &HealthReport) -> Option<f64> {
+    return float_field(report, "phyWrGBNum");
+}
+
+/// Least squares fit of `points`, returns slope and intercept. `None` with
+/// fewer than two distinct x values.
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    return Some((slope, mean_y - slope * mean_x));
+}
+
+/// Fits the wear rate over `records` (one card, any order) and projects when
+/// the remaining life reaches `eol_life_percent`. `None` for an empty history.
+pub fn analyze(records: &[HistoryRecord], eol_life_percent: f64) -> Option<WearTrend> {
+    let mut records: Vec<&HistoryRecord> = records.iter().collect();
+    records.sort_by_key(|record| record.timestamp);
+    let first = records.first()?.timestamp;
+    let last = records.last()?.timestamp;
+    let days = |timestamp: u64| (timestamp - first) as f64 / SECONDS_PER_DAY;
+
+    // Mixing both sources would show a jump where the vendor fields appear,
+    // so the finer one is used only when every sample has it.
+    let sources: Vec<LifeSource> = records.iter()
+        .filter_map(|record| sample_life(&record.report).map(|(_, source)| source))
+        .collect();
+    let source = if sources.is_empty() {
+        None
+    } else if sources.iter().all(|source| *source == LifeSource::RatedCycles) {
+        Some(LifeSource::RatedCycles)
+    } else {
+        Some(LifeSource::Reported)
+    };
+    let life_points: Vec<(f64, f64)> = records.iter()
+        .filter_map(|record| {
+            let life = match source? {
+                LifeSource::RatedCycles => sample_life(&record.report)?.0,
+                LifeSource::Reported => f64::from(record.report.remaining_life_percent?),
+            };
+            Some((days(record.timestamp), life))
+        })
+        .collect();
+    let written_points: Vec<(f64, f64)> = records.iter()
+        .filter_map(|record| Some((days(record.timestamp), sample_written_tb(&record.report)?)))
+        .collect();
+
+    let life_fit = linear_fit(&life_points);
+    let eol_timestamp = life_fit
+        .filter(|(slope, _)| *slope < 0.0)
+        .map(|(slope, intercept)| {
+            let eol_days = (eol_life_percent - intercept) / slope;
+            (first as f64 + eol_days * SECONDS_PER_DAY).max(0.0) as u64
+        });
+
+    return Some(WearTrend {
+        samples: records.len(),
+        first,
+        last,
+        life_percent: life_points.last().map(|(_, life)| *life),
+        life_source: source,
+        life_per_day: life_fit.map(|(slope, _)| -slope),
+        written_tb_per_day: linear_fit(&written_points).map(|(slope, _)| slope),
+        eol_life_percent,
+        eol_timestamp,
+    });
+}
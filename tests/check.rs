@@ -0,0 +1,101 @@
+use sdmon::check::{evaluate, CheckState, Thresholds};
+use sdmon::config::Config;
+use sdmon::emmc::parse_ext_csd;
+use sdmon::report::HealthReport;
+
+fn report(life: Option<f32>, grown_bad: Option<u64>) -> HealthReport {
+    let mut report = HealthReport::new("Sandisk", "Sandisk");
+    if let Some(life) = life {
+        report.set_remaining_life(life);
+    }
+    report.bad_blocks.grown = grown_bad;
+    report.power_cycles = Some(1234);
+    report
+}
+
+fn thresholds() -> Thresholds {
+    Thresholds { warn_life: Some(20.0), crit_life: Some(10.0), warn_grown_bad: Some(5), crit_grown_bad: Some(10), ..Default::default() }
+}
+
+#[test]
+fn healthy_card_is_ok_with_perfdata() {
+    let result = evaluate(&report(Some(87.0), None), &thresholds());
+
+    assert_eq!(result.state, CheckState::Ok);
+    assert_eq!(result.to_string(), "SDMON OK - Sandisk: life 87% | life=87%;20;10 power_cycles=1234");
+}
+
+#[test]
+fn worst_threshold_decides_the_state() {
+    let result = evaluate(&report(Some(15.0), Some(12)), &thresholds());
+
+    assert_eq!(result.state, CheckState::Critical);
+    assert_eq!(result.state.exit_code(), 2);
+    assert_eq!(
+        result.to_string(),
+        "SDMON CRITICAL - Sandisk: life 15%, grown bad blocks 12 | life=15%;20;10 grown_bad=12;5;10 power_cycles=1234"
+    );
+
+    let result = evaluate(&report(Some(20.0), Some(1)), &thresholds());
+    assert_eq!(result.state, CheckState::Warning);
+}
+
+#[test]
+fn card_without_checked_values_is_unknown() {
+    let result = evaluate(&report(None, None), &thresholds());
+
+    assert_eq!(result.state, CheckState::Unknown);
+    assert_eq!(result.state.exit_code(), 3);
+}
+
+#[test]
+fn emmc_pre_eol_urgent_is_critical() {
+    // PRE_EOL_INFO Urgent, life time estimations not defined.
+    let mut ext_csd = [0u8; 512];
+    ext_csd[267] = 0x03;
+    let report = parse_ext_csd("Samsung", &ext_csd);
+
+    let result = evaluate(&report, &Thresholds::defaults());
+    assert_eq!(result.state, CheckState::Critical);
+    assert_eq!(result.to_string(), "SDMON CRITICAL - eMMC: health status Critical");
+
+    ext_csd[267] = 0x02;
+    ext_csd[268] = 0x02;
+    let result = evaluate(&parse_ext_csd("Samsung", &ext_csd), &Thresholds::defaults());
+    assert_eq!(result.state, CheckState::Warning);
+    assert_eq!(result.to_string(), "SDMON WARNING - eMMC: health status Warning | life=80%;30;10");
+}
+
+#[test]
+fn device_section_overrides_vendor_and_defaults() {
+    let config = Config::parse(r#"
+        [thresholds]
+        warn-life = 25
+
+        [vendor.sandisk]
+        warn-grown-bad = 3
+        crit-life = 12
+
+        [device."/dev/mmcblk1"]
+        crit-life = 15
+
+        [device."0x0000abcd"]
+        crit-grown-bad = 8
+    "#).unwrap();
+
+    let other = config.thresholds("/dev/mmcblk0", None, "Transcend");
+    assert_eq!(other.warn_life, Some(25.0));
+    assert_eq!(other.crit_life, Some(10.0));
+    assert_eq!(other.warn_grown_bad, None);
+
+    let card = config.thresholds("/dev/mmcblk1", Some("0x0000abcd"), "Sandisk");
+    assert_eq!(card.warn_life, Some(25.0));
+    assert_eq!(card.crit_life, Some(15.0));
+    assert_eq!(card.warn_grown_bad, Some(3));
+    assert_eq!(card.crit_grown_bad, Some(8));
+}
+
+#[test]
+fn unknown_config_keys_are_rejected() {
+    assert!(Config::parse("[thresholds]\nwarn-lifetime = 20\n").is_err());
+}
@@ -0,0 +1,17 @@
+use std::process::Command;
+
+fn sdmon(args: &[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_sdmon")).args(args).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage:"), "{:?}", output);
+    output.status.code().unwrap()
+}
+
+#[test]
+fn flags_of_other_subcommands_are_usage_errors() {
+    assert_eq!(sdmon(&["record", "--listen", "127.0.0.1:1", "/dev/null", "-o", "/dev/null"]), 1);
+    assert_eq!(sdmon(&["replay", "--read-only", "session.bin"]), 1);
+    assert_eq!(sdmon(&["trend", "--prom-file", "x.prom", "0x1"]), 1);
+    assert_eq!(sdmon(&["--eol-life", "10", "/dev/null"]), 1);
+    // UNKNOWN per the plugin spec.
+    assert_eq!(sdmon(&["check", "--listen", "127.0.0.1:1", "/dev/null"]), 3);
+}
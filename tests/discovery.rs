@@ -0,0 +1,65 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use sdmon::discovery::{discover_in, is_whole_card_block, CardKind};
+
+fn write(root: &Path, path: &str, contents: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn fake_sysfs() -> PathBuf {
+    let root = env::temp_dir().join(format!("sdmon-sysfs-{}", process::id()));
+    _ = fs::remove_dir_all(&root);
+
+    // eMMC with boot and RPMB areas
+    write(&root, "class/mmc_host/mmc0/mmc0:0001/type", "MMC\n");
+    write(&root, "class/mmc_host/mmc0/mmc0:0001/block/mmcblk0/dev", "179:0\n");
+    write(&root, "class/mmc_host/mmc0/mmc0:0001/block/mmcblk0boot0/dev", "179:8\n");
+    write(&root, "class/mmc_host/mmc0/mmc0:0001/block/mmcblk0rpmb/dev", "179:24\n");
+    // SD card
+    write(&root, "class/mmc_host/mmc1/mmc1:aaaa/type", "SD\n");
+    write(&root, "class/mmc_host/mmc1/mmc1:aaaa/block/mmcblk1/dev", "179:32\n");
+    // WiFi chip
+    write(&root, "class/mmc_host/mmc2/mmc2:0001/type", "SDIO\n");
+    write(&root, "class/mmc_host/mmc2/power/control", "auto\n");
+    // Block devices, one of them not reachable through a host
+    write(&root, "block/mmcblk0/dev", "179:0\n");
+    write(&root, "block/mmcblk0boot0/dev", "179:8\n");
+    write(&root, "block/mmcblk1/dev", "179:32\n");
+    write(&root, "block/mmcblk3/device/type", "SD\n");
+    write(&root, "block/sda/dev", "8:0\n");
+
+    root
+}
+
+#[test]
+fn partitions_and_hardware_partitions_are_not_cards() {
+    assert!(is_whole_card_block("mmcblk0"));
+    assert!(is_whole_card_block("mmcblk12"));
+    assert!(!is_whole_card_block("mmcblk0p1"));
+    assert!(!is_whole_card_block("mmcblk0boot1"));
+    assert!(!is_whole_card_block("mmcblk0rpmb"));
+    assert!(!is_whole_card_block("mmcblk"));
+    assert!(!is_whole_card_block("sda"));
+}
+
+#[test]
+fn cards_are_found_through_hosts_and_block_devices() {
+    let root = fake_sysfs();
+    let cards = discover_in(&root);
+    fs::remove_dir_all(&root).unwrap();
+
+    let summary: Vec<(CardKind, Option<String>)> = cards.iter().map(|card| (card.kind, card.device())).collect();
+
+    assert_eq!(summary, vec![
+        (CardKind::Sdio, None),
+        (CardKind::Mmc, Some("/dev/mmcblk0".to_string())),
+        (CardKind::Sd, Some("/dev/mmcblk1".to_string())),
+        (CardKind::Sd, Some("/dev/mmcblk3".to_string())),
+    ]);
+    assert!(!cards[0].kind.has_storage());
+}
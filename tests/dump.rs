@@ -0,0 +1,48 @@
+use sdmon::decode_block;
+use sdmon::error::SdmonError;
+use sdmon::mmc_ioc_cmd::{Cmd56, SDB1, SD_BLOCK_SIZE};
+use sdmon::CMD56_SMART_READ;
+
+fn block_with_signature(b0: u8, b1: u8) -> SDB1 {
+    let mut bytes = [0; SD_BLOCK_SIZE];
+    bytes[0] = b0;
+    bytes[1] = b1;
+    SDB1::from(bytes)
+}
+
+#[test]
+fn debug_dump_text_parses_back() {
+    let mut bytes = [0; SD_BLOCK_SIZE];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let text = format!("[src/mmc_ioc_cmd.rs:1] command = ...\n{}", SDB1::from(bytes));
+
+    let parsed: SDB1 = text.parse().unwrap();
+
+    assert_eq!(parsed.data(), &bytes);
+}
+
+#[test]
+fn truncated_dump_is_rejected() {
+    assert!(matches!("=== Begin buffer dump ===\n00 11\n=== End buffer dump ===".parse::<SDB1>(), Err(SdmonError::Parse(_))));
+}
+
+#[test]
+fn innodisk_needs_its_command() {
+    let detection = decode_block(None, block_with_signature(0x4c, 0x58)).unwrap();
+    assert_eq!(detection.command, Some(Cmd56::LongsysM9H));
+    assert_eq!(detection.report.vendor, "Innodisk");
+
+    let err = decode_block(Some(Cmd56::Transcend as u32), block_with_signature(0x4c, 0x58)).err().unwrap();
+    assert!(matches!(err, SdmonError::UnknownSignature { .. }));
+}
+
+#[test]
+fn smart_argument_selects_the_generic_parser() {
+    let detection = decode_block(Some(CMD56_SMART_READ), block_with_signature(0x70, 0x58)).unwrap();
+    assert_eq!(detection.report.vendor, "Generic");
+
+    let detection = decode_block(None, block_with_signature(0x00, 0x00)).unwrap();
+    assert_eq!(detection.cmd56_arg, CMD56_SMART_READ);
+}
@@ -0,0 +1,120 @@
+//! Runs every block in `tests/fixtures/<vendor>/*.bin` through the parser the
+//! live probe would pick and compares the report with `<name>.json` next to it.
+//!
+//! Set `SDMON_BLESS=1` to (re)write the expected files after an intended change.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sdmon::emmc::{parse_ext_csd, parse_vendor_report, EmmcVendorCommand};
+use sdmon::mmc_ioc_cmd::{Cmd56, SDB1};
+use sdmon::parsers::{get_parsers, get_smartdata_parser};
+use sdmon::report::HealthReport;
+
+/// CMD56 argument each vendor directory was read with, `None` for the
+/// generic SMART sequence. The `emmc` directory holds EXT_CSD registers, the
+/// `emmc_*` directories eMMC vendor health reports.
+fn vendor_command(vendor: &str) -> Option<Cmd56> {
+    match vendor {
+        "sandisk" | "wd" | "longsys" => Some(Cmd56::Sandisk),
+        "micron" => Some(Cmd56::Micron),
+        "swissbit" => Some(Cmd56::Swissbit),
+        "transcend" => Some(Cmd56::Transcend),
+        "innodisk" => Some(Cmd56::LongsysM9H),
+        "adata" => Some(Cmd56::AData),
+        "smart" => None,
+        _ => panic!("no command known for fixture directory {}", vendor),
+    }
+}
+
+/// Every block of the corpus. All of them are synthesized from the documented
+/// layouts, see `tests/fixtures/README.md`; real card dumps are still missing.
+const EXPECTED_FIXTURES: &[&str] = &[
+    "adata/synthesized_industrial_iusl.bin",
+    "emmc/synthesized_ext_csd_rev8.bin",
+    "emmc_micron/synthesized_mtfc8g.bin",
+    "emmc_samsung/synthesized_klmag2ge.bin",
+    "innodisk/synthesized_sd_3te4.bin",
+    "longsys/synthesized_industrial_32g.bin",
+    "micron/synthesized_i400.bin",
+    "sandisk/synthesized_industrial_xi.bin",
+    "smart/synthesized_phison_ps8036.bin",
+    "swissbit/synthesized_s56_8g.bin",
+    "transcend/synthesized_high_endurance_32g.bin",
+    "wd/synthesized_purple_qd101.bin",
+];
+
+fn parse(vendor: &str, block: &SDB1) -> HealthReport {
+    match vendor {
+        "emmc" => return parse_ext_csd("eMMC", block.data()),
+        "emmc_micron" => return parse_vendor_report(EmmcVendorCommand::Micron, block).expect("not a Micron report"),
+        "emmc_samsung" => return parse_vendor_report(EmmcVendorCommand::Samsung, block).expect("not a Samsung report"),
+        _ => {}
+    }
+    let Some(command) = vendor_command(vendor) else {
+        return get_smartdata_parser().parse(block.data());
+    };
+
+    let mut matching = get_parsers().into_iter().filter(|parser| parser.check_signature(command, block.data()));
+    let parser = matching.next().expect("no parser recognized the block");
+    assert!(matching.next().is_none(), "more than one parser recognized the block");
+    parser.parse(block.data())
+}
+
+fn fixtures() -> Vec<(String, PathBuf)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures = Vec::new();
+
+    for vendor_dir in fs::read_dir(root).unwrap() {
+        let vendor_dir = vendor_dir.unwrap().path();
+        if !vendor_dir.is_dir() {
+            continue;
+        }
+        let vendor = vendor_dir.file_name().unwrap().to_string_lossy().to_string();
+        for file in fs::read_dir(&vendor_dir).unwrap() {
+            let path = file.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                fixtures.push((vendor.clone(), path));
+            }
+        }
+    }
+
+    fixtures.sort_by(|a, b| a.1.cmp(&b.1));
+    fixtures
+}
+
+#[test]
+fn vendor_parsers_match_golden_files() {
+    let bless = env::var_os("SDMON_BLESS").is_some();
+    let fixtures = fixtures();
+    let mut mismatches = Vec::new();
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let found: Vec<String> = fixtures.iter()
+        .map(|(_, path)| path.strip_prefix(&root).unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(found, EXPECTED_FIXTURES, "fixture corpus differs from EXPECTED_FIXTURES");
+
+    for (vendor, path) in fixtures {
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 512, "{} is not a 512 byte block", path.display());
+
+        let report = parse(&vendor, &SDB1::from_slice(&bytes));
+        let actual = serde_json::to_string_pretty(&report).unwrap() + "\n";
+        let expected_path = path.with_extension("json");
+
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("{} is missing, run with SDMON_BLESS=1", expected_path.display()));
+        if expected != actual {
+            mismatches.push(format!("--- {}\n{}", expected_path.display(), actual));
+        }
+    }
+
+    assert!(mismatches.is_empty(), "reports differ from the golden files:\n{}", mismatches.join("\n"));
+}
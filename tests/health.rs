@@ -0,0 +1,40 @@
+use sdmon::mmc_ioc_cmd::{Cmd56, SDB1, SD_BLOCK_SIZE};
+use sdmon::parse_vendor_block;
+use sdmon::report::{HealthReport, HealthStatus};
+
+fn sandisk_block(percent_used: u8) -> SDB1 {
+    let mut block = [0; SD_BLOCK_SIZE];
+    block[0] = 0x44;
+    block[1] = 0x53;
+    block[8] = percent_used;
+    SDB1::from(block)
+}
+
+#[test]
+fn status_follows_remaining_life() {
+    assert_eq!(HealthStatus::from_remaining_life(100.0), HealthStatus::Good);
+    assert_eq!(HealthStatus::from_remaining_life(30.5), HealthStatus::Good);
+    assert_eq!(HealthStatus::from_remaining_life(30.0), HealthStatus::Warning);
+    assert_eq!(HealthStatus::from_remaining_life(10.0), HealthStatus::Critical);
+    assert_eq!(HealthStatus::from_remaining_life(0.0), HealthStatus::Critical);
+    assert_eq!(HealthReport::new("x", "y").status, HealthStatus::Unknown);
+}
+
+#[test]
+fn worst_status_prefers_known_and_severe() {
+    assert_eq!(HealthStatus::Unknown.worst(HealthStatus::Good), HealthStatus::Good);
+    assert_eq!(HealthStatus::Good.worst(HealthStatus::Unknown), HealthStatus::Good);
+    assert_eq!(HealthStatus::Warning.worst(HealthStatus::Critical), HealthStatus::Critical);
+    assert_eq!(HealthStatus::Critical.worst(HealthStatus::Good), HealthStatus::Critical);
+}
+
+#[test]
+fn sandisk_percent_used_is_inverted_and_clamped() {
+    let report = parse_vendor_block(Cmd56::Sandisk, &sandisk_block(75)).unwrap();
+    assert_eq!(report.remaining_life_percent, Some(25.0));
+    assert_eq!(report.status, HealthStatus::Warning);
+
+    let report = parse_vendor_block(Cmd56::Sandisk, &sandisk_block(130)).unwrap();
+    assert_eq!(report.remaining_life_percent, Some(0.0));
+    assert_eq!(report.status, HealthStatus::Critical);
+}
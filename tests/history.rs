@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+use sdmon::config::parse_duration;
+use sdmon::history::{format_timestamp, HistoryRecord, HistoryStore};
+use sdmon::mmc_ioc_cmd::SDB1;
+use sdmon::registers::{CardRegisters, Cid};
+use sdmon::report::HealthReport;
+use sdmon::Detection;
+
+fn detection(serial: u32, life: f32, erase_avg: u64) -> Detection {
+    let mut report = HealthReport::new("Swissbit", "Swissbit Micron");
+    report.add_with_unit("User area average cycle cnt", erase_avg, "cycles");
+    report.set_remaining_life(life);
+    report.erase_count.avg = Some(erase_avg);
+
+    let mut detection = Detection::new(None, 0x53420001, SDB1::from_slice(&[0x53, 0x77]), report);
+    detection.registers = CardRegisters {
+        cid: Some(Cid { serial_number: serial, ..Default::default() }),
+        ..Default::default()
+    };
+    detection
+}
+
+#[test]
+fn records_are_appended_and_filtered_by_serial() {
+    let path = env::temp_dir().join(format!("sdmon-history-{}", process::id())).join("history.jsonl");
+    let store = HistoryStore::new(&path);
+
+    assert!(store.records().unwrap().is_empty());
+
+    store.append(&HistoryRecord::new(2000, "/dev/mmcblk0", &detection(0xabcd, 90.0, 300))).unwrap();
+    store.append(&HistoryRecord::new(1000, "/dev/mmcblk0", &detection(0xabcd, 95.0, 150))).unwrap();
+    store.append(&HistoryRecord::new(1500, "/dev/mmcblk1", &detection(0x1234, 50.0, 10))).unwrap();
+
+    assert_eq!(store.records().unwrap().len(), 3);
+
+    let series = store.series("0x0000ABCD").unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].timestamp, 1000);
+    assert_eq!(series[1].report.erase_count.avg, Some(300));
+    assert_eq!(series[1].report, detection(0xabcd, 90.0, 300).report);
+    assert_eq!(series[1].raw[..2], [0x53, 0x77]);
+    assert_eq!(store.series("00001234").unwrap().len(), 1);
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn malformed_lines_are_reported_with_their_number() {
+    let path = env::temp_dir().join(format!("sdmon-history-bad-{}.jsonl", process::id()));
+    fs::write(&path, "\nnot json\n").unwrap();
+
+    let err = HistoryStore::new(&path).records().unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{}", err);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn timestamps_are_formatted_as_utc() {
+    assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_timestamp(1792324245), "2026-10-18T11:50:45Z");
+}
+
+#[test]
+fn durations_accept_units() {
+    assert_eq!(parse_duration("6h"), Some(Duration::from_secs(21600)));
+    assert_eq!(parse_duration("30m"), Some(Duration::from_secs(1800)));
+    assert_eq!(parse_duration("1d"), Some(Duration::from_secs(86400)));
+    assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_duration("0s"), None);
+    assert_eq!(parse_duration("6x"), None);
+    assert_eq!(parse_duration("h"), None);
+}
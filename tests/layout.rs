@@ -0,0 +1,54 @@
+use sdmon::layout::{Endian, EnumMap, FieldSpec, Format, Layout, Role, Source};
+use sdmon::mmc_ioc_cmd::SD_BLOCK_SIZE;
+use sdmon::report::FieldValue;
+
+static STATE: EnumMap = EnumMap { values: &[(1, "ready")], unknown: "Unknown" };
+
+static TEST_LAYOUT: Layout = Layout {
+    vendor: "Test",
+    card_type: "Test card",
+    fields: &[
+        FieldSpec::be("big", 0, 2).role(Role::PowerCycles),
+        FieldSpec::le("little", 0, 2),
+        FieldSpec::bytes("written", 4, 2, Format::Scaled(Endian::Big, 1024.0)).unit("TB"),
+        FieldSpec::bytes("state", 6, 1, Format::Enum(&STATE)),
+        FieldSpec::bytes("other state", 7, 1, Format::Enum(&STATE)),
+        FieldSpec::new("split", Source::Scattered(&[9, 8]), Format::Uint(Endian::Big)),
+        FieldSpec::bytes("bad", 10, 3, Format::Sum).role(Role::GrownBadBlocks),
+        FieldSpec::u8("used", 13).unit("%").role(Role::LifeUsed),
+        FieldSpec::bytes("name", 14, 3, Format::PrintableAscii),
+    ],
+};
+
+fn block() -> [u8; SD_BLOCK_SIZE] {
+    let mut block = [0; SD_BLOCK_SIZE];
+    block[..17].copy_from_slice(&[0x01, 0x02, 0, 0, 0x08, 0x00, 1, 7, 0x34, 0x12, 1, 2, 3, 40, b'a', 0x01, b'b']);
+    block
+}
+
+#[test]
+fn engine_decodes_fields_in_table_order() {
+    let report = TEST_LAYOUT.parse(&block());
+    let value = |name: &str| report.field(name).unwrap().value.clone();
+
+    assert_eq!(report.vendor, "Test");
+    assert_eq!(report.fields.len(), TEST_LAYOUT.fields.len());
+    assert_eq!(value("big"), FieldValue::Uint(0x0102));
+    assert_eq!(value("little"), FieldValue::Uint(0x0201));
+    assert_eq!(value("written"), FieldValue::Float(2.0));
+    assert_eq!(report.field("written").unwrap().unit.as_deref(), Some("TB"));
+    assert_eq!(value("state"), FieldValue::Text("ready".to_string()));
+    assert_eq!(value("other state"), FieldValue::Text("Unknown (7)".to_string()));
+    assert_eq!(value("split"), FieldValue::Uint(0x1234));
+    assert_eq!(value("name"), FieldValue::Text("a_b".to_string()));
+}
+
+#[test]
+fn roles_fill_normalized_members() {
+    let report = TEST_LAYOUT.parse(&block());
+
+    assert_eq!(report.power_cycles, Some(0x0102));
+    assert_eq!(report.bad_blocks.grown, Some(6));
+    assert_eq!(report.remaining_life_percent, Some(60.0));
+    assert_eq!(report.spare_blocks, None);
+}
@@ -0,0 +1,132 @@
+use nix::errno::Errno;
+
+use sdmon::config::Config;
+use sdmon::error::SdmonError;
+use sdmon::mmc_ioc_cmd::{Cmd56, CMDS56, SD_GEN_CMD};
+use sdmon::policy::{ProbePolicy, ProbeRule};
+use sdmon::registers::{CardRegisters, Cid};
+use sdmon::transport::{CommandOptions, ScriptedTransport};
+use sdmon::emmc::MMC_SEND_EXT_CSD;
+use sdmon::{probe_card_with, probe_emmc_with, ReadOptions};
+
+fn cid(mid: u8, oem: &str, product: &str) -> Cid {
+    Cid { manufacturer_id: mid, oem_id: oem.to_string(), product_name: product.to_string(), ..Default::default() }
+}
+
+const CONFIG: &str = r#"
+[[probe]]
+mid = 0x74
+product = "USD00"
+commands = [0x110005F9, 0x110005FB]
+never = [0x110005FB, 0x10]
+postsleep-min-us = 1000
+cmd-timeout-ms = 500
+
+[[probe]]
+never = [0x53420001]
+"#;
+
+#[test]
+fn first_matching_rule_shapes_the_policy() {
+    let config = Config::parse(CONFIG).unwrap();
+
+    let policy = ProbePolicy::resolve(Some(&cid(0x74, "JE", "USD00")), &config.probe);
+    assert_eq!(policy.commands, [Cmd56::Transcend]);
+    assert!(!policy.smart);
+    assert_eq!(policy.options.postsleep_min_us, 1000);
+    assert_eq!(policy.options.postsleep_max_us, 1000);
+    assert_eq!(policy.options.cmd_timeout_ms, 500);
+
+    // Other Transcend products get the built-in commands and the catch-all rule.
+    let policy = ProbePolicy::resolve(Some(&cid(0x74, "JE", "USD01")), &config.probe);
+    assert_eq!(policy.commands, [Cmd56::Transcend]);
+    assert!(policy.smart);
+    assert_eq!(policy.options.postsleep_min_us, 0);
+
+    let policy = ProbePolicy::resolve(None, &config.probe);
+    assert_eq!(policy.commands.len(), CMDS56.len() - 1);
+    assert!(!policy.commands.contains(&Cmd56::Swissbit));
+}
+
+#[test]
+fn rules_with_keys_need_a_cid() {
+    let rule = ProbeRule { mid: Some(0x74), ..Default::default() };
+
+    assert!(!rule.matches(None));
+    assert!(rule.matches(Some(&cid(0x74, "JE", "X"))));
+    assert!(!rule.matches(Some(&cid(0x03, "SD", "X"))));
+    assert!(ProbeRule::default().matches(None));
+}
+
+#[test]
+fn unknown_commands_are_rejected() {
+    let err = Config::parse("[[probe]]\ncommands = [0x12345678]\n").err().unwrap();
+    assert!(err.to_string().contains("probe rule 1: unknown CMD56 argument 0x12345678"), "{}", err);
+
+    let err = Config::parse("[[probe]]\npostsleep-min-us = 10\npostsleep-max-us = 5\n").err().unwrap();
+    assert!(matches!(err, SdmonError::Parse(_)));
+}
+
+#[test]
+fn probe_sends_only_what_the_policy_allows() {
+    let config = Config::parse(CONFIG).unwrap();
+    let registers = CardRegisters { cid: Some(cid(0x74, "JE", "USD00")), ..Default::default() };
+    let mut transport = ScriptedTransport::new().fail(SD_GEN_CMD, Cmd56::Transcend as u32, Errno::EIO);
+
+    let err = probe_card_with(&mut transport, &registers, &ReadOptions::new(false).probe_rules(config.probe)).err().unwrap();
+
+    assert!(matches!(err, SdmonError::Unsupported));
+    assert_eq!(transport.sent().len(), 1);
+    assert_eq!(transport.sent()[0].options.postsleep_min_us, 1000);
+    assert_eq!(transport.sent()[0].options.cmd_timeout_ms, 500);
+}
+
+#[test]
+fn command_line_options_override_vendor_defaults_and_rules() {
+    let config = Config::parse(CONFIG).unwrap();
+
+    let policy = ProbePolicy::resolve(Some(&cid(0x5D, "SB", "SFSD")), &config.probe);
+    assert_eq!(policy.options.postsleep_min_us, 200_000);
+    assert_eq!(policy.options.postsleep_max_us, 250_000);
+    assert_eq!(policy.options.retries, 2);
+
+    let registers = CardRegisters { cid: Some(cid(0x74, "JE", "USD00")), ..Default::default() };
+    let mut transport = ScriptedTransport::new().fail(SD_GEN_CMD, Cmd56::Transcend as u32, Errno::EIO);
+    let options = ReadOptions::new(false)
+        .probe_rules(config.probe)
+        .command_options(CommandOptions::new().post_sleep_us(5000, 5000));
+
+    probe_card_with(&mut transport, &registers, &options).err().unwrap();
+
+    assert_eq!(transport.sent()[0].options.postsleep_min_us, 5000);
+    assert_eq!(transport.sent()[0].options.postsleep_max_us, 5000);
+    assert_eq!(transport.sent()[0].options.cmd_timeout_ms, 500);
+}
+
+#[test]
+fn emmc_commands_get_the_rule_timings_and_the_command_line_options() {
+    let config = Config::parse("[[probe]]\nmid = 0x15\npostsleep-min-us = 2000\ncmd-timeout-ms = 700\n").unwrap();
+    let registers = CardRegisters {
+        cid: Some(Cid::decode_mmc(&[0x15, 0x01, 0x00, 0x52, 0x4A, 0x35, 0x35, 0x41, 0, 0, 0, 0, 0, 0, 0, 0])),
+        ..Default::default()
+    };
+    let mut transport = ScriptedTransport::new()
+        .fail(MMC_SEND_EXT_CSD, 0, Errno::ETIMEDOUT)
+        .respond(MMC_SEND_EXT_CSD, 0, &[0; 512])
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x0000CCEE, &[])
+        .respond(17, 0x1000, &[0; 512])
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x00DECCEE, &[]);
+    let options = ReadOptions::new(false)
+        .probe_rules(config.probe)
+        .command_options(CommandOptions::new().cmd_timeout_ms(900));
+
+    probe_emmc_with(&mut transport, &registers, &options).unwrap();
+
+    assert_eq!(transport.remaining(), 0);
+    for command in transport.sent() {
+        assert_eq!(command.options.postsleep_min_us, 2000, "CMD{}", command.opcode);
+        assert_eq!(command.options.cmd_timeout_ms, 900, "CMD{}", command.opcode);
+    }
+}
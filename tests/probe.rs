@@ -0,0 +1,521 @@
+use nix::errno::Errno;
+
+use sdmon::error::SdmonError;
+use sdmon::mmc_ioc_cmd::{send_multi_cmd, Cmd56, MultiCmdTransport, CMDS56, SD_BLOCK_SIZE, SD_GEN_CMD};
+use sdmon::registers::{CardRegisters, Cid};
+use sdmon::transport::{send_each, MmcCommand, MmcResponse, MmcTransport, ReadOnlyTransport, ScriptedTransport};
+use sdmon::emmc::MMC_SEND_EXT_CSD;
+use sdmon::json::to_json;
+use sdmon::status::{CardState, ILLEGAL_COMMAND};
+use sdmon::discovery::CardKind;
+use sdmon::policy::ProbePolicy;
+use sdmon::{probe_device, probe_policy, ReadOptions};
+use sdmon::{probe, probe_card, probe_commands, probe_emmc, CMD56_SMART_ENABLE, CMD56_SMART_READ};
+
+fn block_with_signature(b0: u8, b1: u8) -> Vec<u8> {
+    let mut block = vec![0; SD_BLOCK_SIZE];
+    block[0] = b0;
+    block[1] = b1;
+    block
+}
+
+/// Script in which every vendor command of the sweep fails.
+fn all_vendor_commands_fail() -> ScriptedTransport {
+    let mut transport = ScriptedTransport::new();
+    for cmd in CMDS56 {
+        transport = transport.fail(SD_GEN_CMD, cmd as u32, Errno::EIO);
+    }
+    transport
+}
+
+#[test]
+fn vendor_signature_stops_the_sweep() {
+    let mut block = block_with_signature(0x54, 0x72);
+    block[70] = 87;
+
+    let mut transport = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, Cmd56::Sandisk as u32, Errno::EIO)
+        .fail(SD_GEN_CMD, Cmd56::Micron as u32, Errno::EIO)
+        .fail(SD_GEN_CMD, Cmd56::Swissbit as u32, Errno::ETIMEDOUT)
+        .respond(SD_GEN_CMD, Cmd56::Transcend as u32, &block);
+
+    let detection = probe(&mut transport, false).unwrap();
+
+    assert_eq!(detection.cmd56_arg, Cmd56::Transcend as u32);
+    assert_eq!(detection.report.vendor, "Transcend");
+    assert_eq!(detection.report.remaining_life_percent, Some(87.0));
+    assert_eq!(transport.sent().len(), 4);
+    assert_eq!(transport.remaining(), 0);
+}
+
+#[test]
+fn unrecognized_blocks_are_skipped() {
+    let mut transport = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, Cmd56::Sandisk as u32, &block_with_signature(0xFF, 0xFF))
+        .respond(SD_GEN_CMD, Cmd56::Micron as u32, &block_with_signature(0x4d, 0x45));
+
+    let detection = probe(&mut transport, false).unwrap();
+
+    assert_eq!(detection.cmd56_arg, Cmd56::Micron as u32);
+    assert_eq!(detection.report.vendor, "Micron");
+}
+
+#[test]
+fn falls_back_to_generic_smart_sequence() {
+    let mut block = block_with_signature(0x98, 0x3A);
+    block[112..116].copy_from_slice(&[0, 0, 0x04, 0xD2]);
+
+    let mut transport = all_vendor_commands_fail()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block);
+
+    let detection = probe(&mut transport, false).unwrap();
+
+    assert_eq!(detection.command, None);
+    assert_eq!(detection.cmd56_arg, CMD56_SMART_READ);
+    assert_eq!(detection.report.vendor, "Generic");
+    assert_eq!(detection.report.power_cycles, Some(1234));
+
+    let enable = &transport.sent()[CMDS56.len()];
+    assert!(enable.write);
+    assert_eq!(enable.data.len(), SD_BLOCK_SIZE);
+}
+
+/// Scripted transport which notes the size of every batch.
+struct BatchingTransport {
+    inner: ScriptedTransport,
+    batches: Vec<usize>,
+}
+
+impl MmcTransport for BatchingTransport {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError> {
+        self.inner.send(command)
+    }
+
+    fn send_batch(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+        self.batches.push(commands.len());
+        send_each(&mut self.inner, commands)
+    }
+}
+
+#[test]
+fn smart_enable_and_read_are_one_batch() {
+    let inner = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+    let mut transport = BatchingTransport { inner, batches: Vec::new() };
+
+    let detection = probe_commands(&mut transport, &[], false).unwrap();
+
+    assert_eq!(detection.cmd56_arg, CMD56_SMART_READ);
+    assert_eq!(transport.batches, [2]);
+}
+
+#[test]
+fn smart_read_is_retried_alone_when_the_enable_fails() {
+    let inner = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, CMD56_SMART_ENABLE, Errno::EIO)
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+    let mut transport = BatchingTransport { inner, batches: Vec::new() };
+
+    let detection = probe_commands(&mut transport, &[], false).unwrap();
+
+    assert_eq!(detection.report.vendor, "Generic");
+    assert_eq!(transport.batches, [2]);
+    assert_eq!(transport.inner.remaining(), 0);
+}
+
+#[test]
+fn read_only_mode_skips_the_smart_enable() {
+    // The read is still tried alone; when it fails too the refusal is reported.
+    let mut card = all_vendor_commands_fail().fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EIO);
+    let mut transport = ReadOnlyTransport::new(&mut card);
+
+    let err = probe(&mut transport, false).err().unwrap();
+
+    assert!(matches!(err, SdmonError::WriteRefused { opcode: SD_GEN_CMD, arg: CMD56_SMART_ENABLE }));
+    assert_eq!(err.exit_code(), 10);
+    assert_eq!(transport.refused().len(), 1);
+    assert_eq!(card.sent().len(), CMDS56.len() + 1);
+    assert_eq!(card.remaining(), 0);
+    assert!(card.sent().iter().all(|command| !command.write));
+}
+
+#[test]
+fn read_only_mode_keeps_vendor_reads() {
+    let mut block = block_with_signature(0x53, 0x77);
+    block[80] = 42;
+    let mut card = ScriptedTransport::new().respond(SD_GEN_CMD, Cmd56::Swissbit as u32, &block);
+    let mut transport = ReadOnlyTransport::new(&mut card);
+
+    let detection = probe_card(&mut transport, &registers_with_mid(0x5D), false).unwrap();
+
+    assert_eq!(detection.report.remaining_life_percent, Some(42.0));
+    assert!(transport.refused().is_empty());
+}
+
+#[test]
+fn card_rejecting_every_command_is_unsupported() {
+    let mut transport = all_vendor_commands_fail()
+        .fail(SD_GEN_CMD, CMD56_SMART_ENABLE, Errno::EIO)
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EIO);
+
+    let err = probe(&mut transport, false).err().unwrap();
+
+    assert!(matches!(err, SdmonError::Unsupported));
+    assert_eq!(err.exit_code(), 6);
+}
+
+#[test]
+fn unknown_signature_keeps_the_block() {
+    let mut transport = ScriptedTransport::new();
+    for cmd in CMDS56 {
+        transport = if cmd == Cmd56::Atp {
+            transport.respond(SD_GEN_CMD, cmd as u32, &block_with_signature(0x12, 0x34))
+        } else {
+            transport.fail(SD_GEN_CMD, cmd as u32, Errno::EIO)
+        };
+    }
+    transport = transport
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EIO);
+
+    match probe(&mut transport, false) {
+        Err(SdmonError::UnknownSignature { cmd56_arg, block, .. }) => {
+            assert_eq!(cmd56_arg, Cmd56::Atp as u32);
+            assert_eq!(block[0..2], [0x12, 0x34]);
+        }
+        _ => panic!("expected an unknown signature"),
+    }
+}
+
+#[test]
+fn permission_errors_are_not_reported_as_unsupported() {
+    let mut transport = ScriptedTransport::new();
+    for cmd in CMDS56 {
+        transport = transport.fail(SD_GEN_CMD, cmd as u32, Errno::EPERM);
+    }
+    transport = transport
+        .fail(SD_GEN_CMD, CMD56_SMART_ENABLE, Errno::EPERM)
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EPERM);
+
+    let err = probe(&mut transport, false).err().unwrap();
+
+    assert!(matches!(err, SdmonError::Ioctl { errno: Errno::EPERM, .. }));
+}
+
+fn registers_with_mid(mid: u8) -> CardRegisters {
+    CardRegisters { cid: Some(Cid { manufacturer_id: mid, ..Default::default() }), ..Default::default() }
+}
+
+#[test]
+fn known_manufacturer_goes_straight_to_its_command() {
+    let mut transport = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, Cmd56::Transcend as u32, &block_with_signature(0x54, 0x72));
+
+    let detection = probe_card(&mut transport, &registers_with_mid(0x74), false).unwrap();
+
+    assert_eq!(detection.report.vendor, "Transcend");
+    assert_eq!(transport.sent().len(), 1);
+}
+
+#[test]
+fn known_manufacturer_falls_back_to_smart_only() {
+    let mut transport = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, Cmd56::Swissbit as u32, Errno::EIO)
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+
+    let detection = probe_card(&mut transport, &registers_with_mid(0x5D), false).unwrap();
+
+    assert_eq!(detection.cmd56_arg, CMD56_SMART_READ);
+    assert_eq!(transport.remaining(), 0);
+}
+
+#[test]
+fn unknown_manufacturer_sweeps_everything() {
+    let mut transport = all_vendor_commands_fail()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+
+    probe_card(&mut transport, &registers_with_mid(0xEE), false).unwrap();
+    assert_eq!(transport.remaining(), 0);
+
+    let mut transport = all_vendor_commands_fail()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+
+    probe_card(&mut transport, &CardRegisters::default(), false).unwrap();
+    assert_eq!(transport.remaining(), 0);
+}
+
+#[test]
+fn emmc_health_comes_from_ext_csd() {
+    let mut ext_csd = vec![0; SD_BLOCK_SIZE];
+    ext_csd[192] = 8;
+    ext_csd[267] = 0x02;
+    ext_csd[268] = 0x01;
+    ext_csd[269] = 0x04;
+
+    let mut transport = ScriptedTransport::new().respond(MMC_SEND_EXT_CSD, 0, &ext_csd);
+
+    let detection = probe_emmc(&mut transport, &emmc_registers(0x11), false).unwrap();
+
+    assert_eq!(detection.opcode, MMC_SEND_EXT_CSD);
+    assert_eq!(detection.command, None);
+    assert_eq!(detection.report.vendor, "Toshiba");
+    assert_eq!(detection.report.card_type, "eMMC");
+    assert_eq!(detection.report.remaining_life_percent, Some(60.0));
+    assert_eq!(detection.report.field("Pre EOL info").unwrap().value.to_string(), "Warning");
+    assert_eq!(transport.remaining(), 0);
+}
+
+fn emmc_registers(mid: u8) -> CardRegisters {
+    CardRegisters {
+        cid: Some(Cid::decode_mmc(&[mid, 0x01, 0x00, 0x52, 0x4A, 0x35, 0x35, 0x41, 0, 0, 0, 0, 0, 0, 0, 0])),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn samsung_emmc_smart_report_is_merged_into_ext_csd() {
+    let mut ext_csd = vec![0; SD_BLOCK_SIZE];
+    ext_csd[268] = 0x02;
+    let mut smart = vec![0; SD_BLOCK_SIZE];
+    smart[16] = 1;
+    smart[20] = 4;
+    smart[24] = 2;
+    smart[28] = 60;
+    smart[68] = 200;
+    smart[76] = 150;
+
+    let mut transport = ScriptedTransport::new()
+        .respond(MMC_SEND_EXT_CSD, 0, &ext_csd)
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x0000CCEE, &[])
+        .respond(17, 0x1000, &smart)
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x00DECCEE, &[]);
+
+    let detection = probe_emmc(&mut transport, &emmc_registers(0x15), false).unwrap();
+
+    assert_eq!(detection.report.card_type, "Samsung eMMC");
+    assert_eq!(detection.report.remaining_life_percent, Some(80.0));
+    assert_eq!(detection.report.spare_blocks, Some(60));
+    assert_eq!(detection.report.bad_blocks.grown, Some(2));
+    assert_eq!(detection.report.erase_count.max, Some(200));
+    assert!(detection.report.field("Life time estimation A (SLC)").is_some());
+    assert_eq!(detection.vendor_block.unwrap().data()[68], 200);
+    assert_eq!(transport.remaining(), 0);
+}
+
+#[test]
+fn samsung_vendor_mode_is_left_when_the_report_read_fails() {
+    let mut transport = ScriptedTransport::new()
+        .respond(MMC_SEND_EXT_CSD, 0, &vec![0; SD_BLOCK_SIZE])
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x0000CCEE, &[])
+        .fail(17, 0x1000, Errno::EILSEQ)
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x00DECCEE, &[]);
+
+    let detection = probe_emmc(&mut transport, &emmc_registers(0x15), false).unwrap();
+
+    assert_eq!(detection.report.card_type, "eMMC");
+    assert!(detection.vendor_block.is_none());
+    assert_eq!(transport.remaining(), 0);
+}
+
+#[test]
+fn transient_errors_are_retried() {
+    let mut transport = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, Cmd56::Transcend as u32, Errno::ETIMEDOUT)
+        .fail(SD_GEN_CMD, Cmd56::Transcend as u32, Errno::EILSEQ)
+        .respond(SD_GEN_CMD, Cmd56::Transcend as u32, &block_with_signature(0x54, 0x72));
+
+    let detection = probe_card(&mut transport, &registers_with_mid(0x74), false).unwrap();
+
+    assert_eq!(detection.report.vendor, "Transcend");
+    assert_eq!(transport.sent().len(), 3);
+}
+
+#[test]
+fn illegal_command_status_is_not_supported() {
+    let mut transport = ScriptedTransport::new()
+        .respond_with_status(SD_GEN_CMD, Cmd56::Transcend as u32, &block_with_signature(0x54, 0x72), ILLEGAL_COMMAND)
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond_with_status(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A), ILLEGAL_COMMAND);
+
+    let err = probe_card(&mut transport, &registers_with_mid(0x74), false).err().unwrap();
+
+    assert!(matches!(err, SdmonError::IllegalCommand { arg: CMD56_SMART_READ, .. }), "{}", err);
+    assert_eq!(err.exit_code(), 11);
+    assert_eq!(transport.remaining(), 0);
+
+    // Only the last failure counts, an I/O error of the SMART read makes the
+    // card unsupported.
+    let mut transport = ScriptedTransport::new()
+        .respond_with_status(SD_GEN_CMD, Cmd56::Transcend as u32, &block_with_signature(0x54, 0x72), ILLEGAL_COMMAND)
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EIO);
+
+    let err = probe_card(&mut transport, &registers_with_mid(0x74), false).err().unwrap();
+    assert!(matches!(err, SdmonError::Unsupported), "{}", err);
+
+    let mut transport = ScriptedTransport::new()
+        .respond_with_status(SD_GEN_CMD, Cmd56::Transcend as u32, &block_with_signature(0x54, 0x72), ILLEGAL_COMMAND);
+    let policy = ProbePolicy { smart: false, ..ProbePolicy::new(&[Cmd56::Transcend]) };
+
+    let err = probe_policy(&mut transport, &policy, false).err().unwrap();
+    assert!(matches!(err, SdmonError::IllegalCommand { arg, .. } if arg == Cmd56::Transcend as u32), "{}", err);
+}
+
+#[test]
+fn detection_keeps_the_card_status() {
+    let mut transport = ScriptedTransport::new()
+        .respond_with_status(SD_GEN_CMD, Cmd56::Transcend as u32, &block_with_signature(0x54, 0x72), 0x900);
+
+    let detection = probe_card(&mut transport, &registers_with_mid(0x74), false).unwrap();
+
+    assert_eq!(detection.status.map(|status| status.current_state()), Some(CardState::Transfer));
+
+    let json: serde_json::Value = serde_json::from_str(&to_json("/dev/mmcblk0", &detection)).unwrap();
+    assert_eq!(json["status"], serde_json::to_value(detection.report.status).unwrap());
+    assert_eq!(json["card_status"]["raw"], "0x00000900");
+    assert_eq!(json["card_status"]["state"], "tran");
+}
+
+/// Scripted transport on a kernel failing every MMC_IOC_MULTI_CMD with
+/// `errno`: EINVAL from `mmc_blk_ioctl` before 4.4, ENOTTY from the block
+/// layer.
+struct OldKernelTransport {
+    inner: ScriptedTransport,
+    errno: Errno,
+    supported: bool,
+    multi_cmds: usize,
+}
+
+impl MmcTransport for OldKernelTransport {
+    fn send(&mut self, command: &MmcCommand) -> Result<MmcResponse, SdmonError> {
+        self.inner.send(command)
+    }
+
+    fn send_batch(&mut self, commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, SdmonError> {
+        send_multi_cmd(self, commands)
+    }
+}
+
+impl MultiCmdTransport for OldKernelTransport {
+    fn multi_cmd(&mut self, _commands: &[MmcCommand]) -> Result<Vec<MmcResponse>, Errno> {
+        self.multi_cmds += 1;
+        Err(self.errno)
+    }
+
+    fn multi_cmd_supported(&self) -> bool {
+        self.supported
+    }
+
+    fn set_multi_cmd_supported(&mut self, supported: bool) {
+        self.supported = supported;
+    }
+}
+
+#[test]
+fn batches_fall_back_to_single_commands_on_einval_and_enotty() {
+    let inner = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+    let mut transport = OldKernelTransport { inner, errno: Errno::EINVAL, supported: true, multi_cmds: 0 };
+
+    let detection = probe_commands(&mut transport, &[], false).unwrap();
+
+    assert_eq!(detection.report.vendor, "Generic");
+    assert_eq!(transport.inner.remaining(), 0);
+
+    // EINVAL may come from a bad command as well, so MULTI_CMD is tried
+    // again for the Samsung vendor mode sequence, which then goes out one by
+    // one too.
+    let mut smart = vec![0; SD_BLOCK_SIZE];
+    smart[16] = 1;
+    smart[20] = 4;
+    smart[28] = 60;
+    transport.inner = ScriptedTransport::new()
+        .respond(MMC_SEND_EXT_CSD, 0, &vec![0; SD_BLOCK_SIZE])
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x0000CCEE, &[])
+        .respond(17, 0x1000, &smart)
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x00DECCEE, &[]);
+
+    let detection = probe_emmc(&mut transport, &emmc_registers(0x15), false).unwrap();
+
+    assert_eq!(detection.report.card_type, "Samsung eMMC");
+    assert_eq!(detection.report.spare_blocks, Some(60));
+    assert_eq!(transport.inner.remaining(), 0);
+    assert_eq!(transport.multi_cmds, 2);
+    assert!(transport.supported);
+
+    // ENOTTY means no MULTI_CMD at all, it is not tried again and the Samsung
+    // sequence still goes out in full.
+    transport.errno = Errno::ENOTTY;
+    transport.multi_cmds = 0;
+    transport.inner = ScriptedTransport::new()
+        .respond(MMC_SEND_EXT_CSD, 0, &vec![0; SD_BLOCK_SIZE])
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x0000CCEE, &[])
+        .respond(17, 0x1000, &smart)
+        .respond(62, 0xEFAC62EC, &[])
+        .respond(62, 0x00DECCEE, &[]);
+
+    let detection = probe_emmc(&mut transport, &emmc_registers(0x15), false).unwrap();
+
+    assert_eq!(detection.report.card_type, "Samsung eMMC");
+    assert_eq!(transport.inner.remaining(), 0);
+    assert_eq!(transport.multi_cmds, 1);
+    assert!(!transport.supported);
+
+    transport.inner = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .respond(SD_GEN_CMD, CMD56_SMART_READ, &block_with_signature(0x98, 0x3A));
+    probe_commands(&mut transport, &[], false).unwrap();
+    assert_eq!(transport.multi_cmds, 1);
+}
+
+#[test]
+fn read_only_mode_reports_what_it_skipped() {
+    let mut card = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, Cmd56::Atp as u32, &block_with_signature(0x12, 0x34))
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EIO);
+    let options = ReadOptions::new(false).read_only(true);
+
+    let err = probe_device(&mut card, CardKind::Sd, registers_with_mid(0x09), &options).err().unwrap();
+
+    match &err {
+        SdmonError::UnknownSignature { cmd56_arg, refused, .. } => {
+            assert_eq!(*cmd56_arg, Cmd56::Atp as u32);
+            assert_eq!(refused.len(), 1);
+            assert_eq!(refused[0].arg, CMD56_SMART_ENABLE);
+        }
+        _ => panic!("expected an unknown signature, got {}", err),
+    }
+    assert!(err.to_string().ends_with("CMD56 arg 0x00000010 skipped in read-only mode"), "{}", err);
+    assert_eq!(card.sent().len(), 2);
+}
+
+#[test]
+fn read_only_mode_skips_the_samsung_vendor_mode() {
+    let mut card = ScriptedTransport::new().respond(MMC_SEND_EXT_CSD, 0, &vec![0; SD_BLOCK_SIZE]);
+    let options = ReadOptions::new(false).read_only(true);
+
+    let detection = probe_device(&mut card, CardKind::Mmc, emmc_registers(0x15), &options).unwrap();
+
+    assert_eq!(detection.report.card_type, "eMMC");
+    assert!(detection.vendor_block.is_none());
+    assert_eq!(detection.refused.len(), 1);
+    assert_eq!((detection.refused[0].opcode, detection.refused[0].arg), (62, 0xEFAC62EC));
+    assert_eq!(card.sent().len(), 1);
+    assert_eq!(card.remaining(), 0);
+
+    let json: serde_json::Value = serde_json::from_str(&to_json("/dev/mmcblk0", &detection)).unwrap();
+    assert_eq!(json["refused"][0], "CMD62 arg 0xEFAC62EC");
+}
@@ -0,0 +1,95 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use sdmon::prometheus::{render, write_textfile, DeviceSample};
+use sdmon::report::HealthReport;
+
+fn report() -> HealthReport {
+    let mut report = HealthReport::new("Swissbit", "Swissbit Micron");
+    report.add_with_unit("written", 2.0, "TB");
+    report.add("cycles", 258u32);
+    report.add("state", "ready");
+    report.set_remaining_life(87.0);
+    report.power_cycles = Some(1234);
+    report.bad_blocks.grown = Some(3);
+    report.erase_count.avg = Some(300);
+    report
+}
+
+#[test]
+fn metrics_have_help_type_and_labels() {
+    let report = report();
+    let text = render(&[DeviceSample { device: "/dev/mmcblk0", serial: "0x1", report: Some(&report) }]);
+    let labels = "device=\"/dev/mmcblk0\",vendor=\"Swissbit\",serial=\"0x1\"";
+
+    assert!(text.contains("# TYPE sdmon_probe_success gauge\n"));
+    assert!(text.contains(&format!("sdmon_probe_success{{{}}} 1\n", labels)));
+    assert!(text.contains("# HELP sdmon_remaining_life_percent "));
+    assert!(text.contains("# TYPE sdmon_remaining_life_percent gauge\n"));
+    assert!(text.contains(&format!("sdmon_remaining_life_percent{{{}}} 87\n", labels)));
+    assert!(text.contains("# TYPE sdmon_power_cycles_total counter\n"));
+    assert!(text.contains(&format!("sdmon_power_cycles_total{{{}}} 1234\n", labels)));
+    assert!(text.contains(&format!("sdmon_bad_blocks{{{},kind=\"grown\"}} 3\n", labels)));
+    assert!(text.contains(&format!("sdmon_erase_count{{{},stat=\"avg\"}} 300\n", labels)));
+    assert!(!text.contains("stat=\"max\""));
+    assert!(!text.contains("sdmon_spare_blocks"));
+}
+
+#[test]
+fn numeric_fields_are_exported() {
+    let report = report();
+    let text = render(&[DeviceSample { device: "/dev/mmcblk0", serial: "0x1", report: Some(&report) }]);
+
+    assert!(text.contains("# TYPE sdmon_field gauge\n"));
+    assert!(text.contains("sdmon_field{device=\"/dev/mmcblk0\",vendor=\"Swissbit\",serial=\"0x1\",field=\"written\",unit=\"TB\"} 2\n"));
+    assert!(text.contains("field=\"cycles\",unit=\"\"} 258\n"));
+    assert!(!text.contains("field=\"state\""));
+}
+
+#[test]
+fn failed_probe_only_exports_probe_success() {
+    let report = report();
+    let text = render(&[
+        DeviceSample { device: "/dev/mmcblk0", serial: "0x1", report: Some(&report) },
+        DeviceSample { device: "/dev/mmcblk1", serial: "", report: None },
+    ]);
+
+    assert!(text.contains("sdmon_probe_success{device=\"/dev/mmcblk1\",vendor=\"\",serial=\"\"} 0\n"));
+    assert_eq!(text.matches("device=\"/dev/mmcblk1\"").count(), 1);
+
+    let text = render(&[DeviceSample { device: "/dev/mmcblk1", serial: "", report: None }]);
+    assert_eq!(text.lines().filter(|line| !line.starts_with('#')).count(), 1);
+    assert!(!text.contains("sdmon_remaining_life_percent"));
+}
+
+#[test]
+fn label_values_are_escaped() {
+    let report = report();
+    let text = render(&[DeviceSample { device: "/dev/\"odd\"\\", serial: "a\nb", report: Some(&report) }]);
+
+    assert!(text.contains("device=\"/dev/\\\"odd\\\"\\\\\",vendor=\"Swissbit\",serial=\"a\\nb\""));
+}
+
+#[test]
+fn textfile_is_replaced_atomically() {
+    let dir = env::temp_dir().join(format!("sdmon-textfile-{}", process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("sdmon.prom");
+
+    write_textfile(&path, "old\n").unwrap();
+    write_textfile(&path, "new\n").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+    let names: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+    assert_eq!(names, ["sdmon.prom"]);
+
+    // A failed rename, here over a directory, leaves no temporary file.
+    fs::create_dir_all(dir.join("busy").join("entry")).unwrap();
+    assert!(write_textfile(&dir.join("busy"), "new\n").is_err());
+    let mut names: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+    names.sort();
+    assert_eq!(names, ["busy", "sdmon.prom"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
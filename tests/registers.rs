@@ -0,0 +1,82 @@
+use sdmon::registers::{bits, read_scr, Cid, Csd, Scr, Ssr};
+use sdmon::report::hex;
+use sdmon::transport::ScriptedTransport;
+
+fn register(text: &str) -> Vec<u8> {
+    hex::decode(&text.to_uppercase()).unwrap()
+}
+
+#[test]
+fn bits_are_numbered_from_the_last_byte() {
+    let register = [0x80, 0x01];
+    assert_eq!(bits(&register, 15, 15), 1);
+    assert_eq!(bits(&register, 0, 0), 1);
+    assert_eq!(bits(&register, 14, 1), 0);
+    assert_eq!(bits(&register, 15, 0), 0x8001);
+}
+
+#[test]
+fn sd_cid_is_decoded() {
+    let cid = Cid::decode_sd(&register("035344534333324780ce5f9e3f011100"));
+
+    assert_eq!(cid.manufacturer_id, 0x03);
+    assert_eq!(cid.manufacturer.as_deref(), Some("SanDisk"));
+    assert_eq!(cid.oem_id, "SD");
+    assert_eq!(cid.product_name, "SC32G");
+    assert_eq!(cid.revision, "8.0");
+    assert_eq!(cid.serial_number, 0xce5f9e3f);
+    assert_eq!(cid.manufacturing_date, "2017-01");
+}
+
+#[test]
+fn mmc_cid_is_decoded() {
+    let cid = Cid::decode_mmc(&register("150100424a54443452077f5b5b72a700"));
+
+    assert_eq!(cid.manufacturer_id, 0x15);
+    assert_eq!(cid.oem_id, "0x00");
+    assert_eq!(cid.product_name, "BJTD4R");
+    assert_eq!(cid.serial_number, 0x7f5b5b72);
+}
+
+#[test]
+fn sdhc_csd_capacity() {
+    let csd = Csd::decode_sd(&register("400e00325b590000edc87f800a404000"));
+
+    assert_eq!(csd.structure, 1);
+    assert_eq!(csd.capacity_bytes, 31_914_983_424);
+}
+
+#[test]
+fn scr_spec_version() {
+    let scr = Scr::decode(&register("0235800300000000"));
+
+    assert_eq!(scr.sd_spec, "3.0x");
+    assert_eq!(scr.bus_widths, 0x5);
+}
+
+#[test]
+fn ssr_performance_classes() {
+    let mut ssr = [0u8; 64];
+    ssr[8] = 0x04;
+    ssr[14] = 0x10;
+    ssr[15] = 30;
+    ssr[21] = 0x01;
+
+    let ssr = Ssr::decode(&ssr);
+
+    assert_eq!(ssr.speed_class, Some(10));
+    assert_eq!(ssr.uhs_speed_grade, 1);
+    assert_eq!(ssr.video_speed_class, 30);
+    assert_eq!(ssr.app_perf_class, 1);
+}
+
+#[test]
+fn scr_is_read_with_acmd51() {
+    let mut transport = ScriptedTransport::new().respond(51, 0, &register("0235800300000000"));
+
+    let scr = read_scr(&mut transport).unwrap();
+
+    assert_eq!(scr.sd_spec, "3.0x");
+    assert!(transport.sent()[0].is_acmd);
+    assert_eq!(transport.sent()[0].blksz, 8);
+}
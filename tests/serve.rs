@@ -0,0 +1,106 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use sdmon::mmc_ioc_cmd::{Cmd56, CMDS56, SD_BLOCK_SIZE, SD_GEN_CMD};
+use sdmon::probe;
+use sdmon::serve::{DeviceReader, Exporter};
+use sdmon::transport::ScriptedTransport;
+
+/// Reader answering like a Transcend card with 87% life left on mmcblk0 and
+/// failing on every other device, counting the reads.
+fn mock_reader(reads: Arc<AtomicUsize>) -> DeviceReader {
+    Box::new(move |device: &str| {
+        reads.fetch_add(1, Ordering::SeqCst);
+        let mut transport = ScriptedTransport::new();
+        if device == "/dev/mmcblk0" {
+            let mut block = vec![0; SD_BLOCK_SIZE];
+            block[0] = 0x54;
+            block[1] = 0x72;
+            block[70] = 87;
+            transport = transport
+                .fail(SD_GEN_CMD, Cmd56::Sandisk as u32, Errno::EIO)
+                .fail(SD_GEN_CMD, Cmd56::Micron as u32, Errno::EIO)
+                .fail(SD_GEN_CMD, Cmd56::Swissbit as u32, Errno::EIO)
+                .respond(SD_GEN_CMD, Cmd56::Transcend as u32, &block);
+        } else {
+            for cmd in CMDS56 {
+                transport = transport.fail(SD_GEN_CMD, cmd as u32, Errno::EIO);
+            }
+        }
+        probe(&mut transport, false)
+    })
+}
+
+/// Serves `requests` connections on a local port in the background.
+fn start(exporter: Exporter, requests: usize) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut exporter = exporter;
+    thread::spawn(move || {
+        for _ in 0..requests {
+            let (mut stream, _) = listener.accept().unwrap();
+            exporter.handle_connection(&mut stream).unwrap();
+        }
+    });
+    addr
+}
+
+fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+#[test]
+fn metrics_and_api_are_served_from_the_cache() {
+    let reads = Arc::new(AtomicUsize::new(0));
+    let devices = vec!["/dev/mmcblk0".to_string(), "/dev/mmcblk1".to_string()];
+    let addr = start(Exporter::new(devices, Duration::from_secs(3600), mock_reader(reads.clone())), 5);
+
+    let (status, metrics) = get(addr, "/metrics");
+    assert_eq!(status, 200);
+    assert!(metrics.contains("sdmon_remaining_life_percent{device=\"/dev/mmcblk0\",vendor=\"Transcend\",serial=\"\"} 87"), "{}", metrics);
+    assert!(metrics.contains("sdmon_probe_success{device=\"/dev/mmcblk1\",vendor=\"\",serial=\"\"} 0"), "{}", metrics);
+
+    let (status, body) = get(addr, "/api/devices");
+    assert_eq!(status, 200);
+    let devices: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(devices[0]["vendor"], "Transcend");
+    assert_eq!(devices[0]["remaining_life_percent"], 87.0);
+    assert_eq!(devices[1]["device"], "/dev/mmcblk1");
+    assert!(devices[1]["error"].is_string());
+
+    let (status, body) = get(addr, "/api/devices/mmcblk0");
+    assert_eq!(status, 200);
+    let device: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(device["cmd56_arg"], "0x110005F9");
+
+    assert_eq!(get(addr, "/api/devices/mmcblk7").0, 404);
+    assert_eq!(get(addr, "/").0, 404);
+
+    // Five requests, but every card was read only once.
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn cards_are_read_again_after_the_minimum_interval() {
+    let reads = Arc::new(AtomicUsize::new(0));
+    let mut exporter = Exporter::new(vec!["/dev/mmcblk0".to_string()], Duration::ZERO, mock_reader(reads.clone()));
+
+    assert_eq!(exporter.handle("GET", "/metrics").status, 200);
+    assert_eq!(exporter.handle("GET", "/api/devices/mmcblk0?pretty").status, 200);
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+    assert_eq!(exporter.handle("POST", "/metrics").status, 405);
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,121 @@
+use nix::errno::Errno;
+
+use sdmon::mmc_ioc_cmd::{Cmd56, SD_BLOCK_SIZE, SD_GEN_CMD};
+use sdmon::error::SdmonError;
+use sdmon::config::Config;
+use sdmon::registers::{CardRegisters, Cid};
+use sdmon::{probe, probe_card_with, probe_commands, probe_policy, ReadOptions, CMD56_SMART_ENABLE, CMD56_SMART_READ};
+use sdmon::session::{RecordingTransport, Session};
+use sdmon::transport::{ReadOnlyTransport, ScriptedTransport};
+
+#[test]
+fn recorded_session_replays_to_the_same_report() {
+    let mut block = vec![0; SD_BLOCK_SIZE];
+    block[0] = 0x53;
+    block[1] = 0x77;
+    block[80] = 42;
+
+    let mut card = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, Cmd56::Sandisk as u32, Errno::EIO)
+        .fail(SD_GEN_CMD, Cmd56::Micron as u32, Errno::ETIMEDOUT)
+        .respond(SD_GEN_CMD, Cmd56::Swissbit as u32, &block);
+
+    let mut recorder = RecordingTransport::new("/dev/mmcblk0", &mut card);
+    let recorded = probe(&mut recorder, false).unwrap();
+    let session = Session::from_bytes(&recorder.session().to_bytes()).unwrap();
+
+    assert_eq!(session.device, "/dev/mmcblk0");
+    assert_eq!(session.entries.len(), 3);
+    assert_eq!(session.entries[1].result.as_ref().err(), Some(&Errno::ETIMEDOUT));
+    assert_eq!(session.entries[2].command.flags, recorder.session().entries[2].command.flags);
+
+    let mut replay = session.replay();
+    let replayed = probe(&mut replay, false).unwrap();
+
+    assert_eq!(replayed.report, recorded.report);
+    assert_eq!(replayed.report.remaining_life_percent, Some(42.0));
+    assert_eq!(replay.remaining(), 0);
+}
+
+#[test]
+fn failed_batch_replays_the_same_way() {
+    let mut card = ScriptedTransport::new()
+        .respond(SD_GEN_CMD, CMD56_SMART_ENABLE, &[])
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EILSEQ);
+
+    let mut recorder = RecordingTransport::new("/dev/mmcblk0", &mut card);
+    assert!(probe_commands(&mut recorder, &[], false).is_err());
+    let session = recorder.session().clone();
+
+    assert_eq!(session.entries.len(), 2);
+    assert!(session.entries[0].result.is_ok());
+    assert_eq!(session.entries[1].result.as_ref().err(), Some(&Errno::EILSEQ));
+
+    let mut replay = session.replay();
+    assert!(matches!(probe_commands(&mut replay, &[], false), Err(SdmonError::Unsupported)));
+    assert_eq!(replay.remaining(), 0);
+}
+
+#[test]
+fn recording_follows_the_probe_rules() {
+    let config = Config::parse("[[probe]]\nmid = 0x27\nnever = [0x110005FB, 0x10]\n").unwrap();
+    let registers = CardRegisters { cid: Some(Cid { manufacturer_id: 0x27, ..Default::default() }), ..Default::default() };
+    let mut card = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, Cmd56::Transcend as u32, Errno::EIO)
+        .fail(SD_GEN_CMD, Cmd56::LongsysM9H as u32, Errno::EIO)
+        .fail(SD_GEN_CMD, Cmd56::AData as u32, Errno::EIO);
+
+    let mut recorder = RecordingTransport::new("/dev/mmcblk0", &mut card);
+    let options = ReadOptions::new(false).probe_rules(config.probe.clone());
+    assert!(matches!(probe_card_with(&mut recorder, &registers, &options), Err(SdmonError::Unsupported)));
+    let session = recorder.session().clone();
+
+    assert!(session.entries.iter().all(|entry| entry.command.arg != Cmd56::Micron as u32));
+    let mut replay = session.replay();
+    assert!(matches!(probe_policy(&mut replay, &session.policy(), false), Err(SdmonError::Unsupported)));
+    assert_eq!(replay.remaining(), 0);
+
+    // A full sweep still leaves out what the rule forbids.
+    let mut card = ScriptedTransport::new();
+    let mut recorder = RecordingTransport::new("/dev/mmcblk0", &mut card);
+    let _ = probe_card_with(&mut recorder, &registers, &options.sweep_all(true));
+
+    let sent: Vec<u32> = card.sent().iter().map(|command| command.arg).collect();
+    assert_eq!(sent.len(), 6);
+    assert!(!sent.contains(&(Cmd56::Micron as u32)));
+    assert!(!sent.contains(&CMD56_SMART_ENABLE));
+}
+
+#[test]
+fn read_only_recording_replays_the_same_way() {
+    let mut card = ScriptedTransport::new()
+        .fail(SD_GEN_CMD, Cmd56::Sandisk as u32, Errno::EIO)
+        .fail(SD_GEN_CMD, CMD56_SMART_READ, Errno::EIO);
+    let mut read_only = ReadOnlyTransport::new(&mut card);
+    let mut recorder = RecordingTransport::new("/dev/mmcblk0", &mut read_only);
+    let recorded = probe_commands(&mut recorder, &[Cmd56::Sandisk], false);
+    let session = Session::from_bytes(&recorder.session().to_bytes()).unwrap();
+
+    assert!(matches!(recorded, Err(SdmonError::WriteRefused { arg: CMD56_SMART_ENABLE, .. })));
+    assert!(session.read_only);
+    assert!(matches!(session.probe(false), Err(SdmonError::WriteRefused { arg: CMD56_SMART_ENABLE, .. })));
+
+    // The SMART read alone is still tried and answered by some cards.
+    let mut block = vec![0; SD_BLOCK_SIZE];
+    block[0] = 0x11;
+    let mut card = ScriptedTransport::new().respond(SD_GEN_CMD, CMD56_SMART_READ, &block);
+    let mut read_only = ReadOnlyTransport::new(&mut card);
+    let mut recorder = RecordingTransport::new("/dev/mmcblk0", &mut read_only);
+    let recorded = probe_commands(&mut recorder, &[], false).unwrap();
+    let session = Session::from_bytes(&recorder.session().to_bytes()).unwrap();
+
+    let replayed = session.probe(false).unwrap();
+    assert_eq!(replayed.cmd56_arg, CMD56_SMART_READ);
+    assert_eq!(replayed.report, recorded.report);
+}
+
+#[test]
+fn garbage_is_not_a_session() {
+    assert!(Session::from_bytes(b"not a session").is_err());
+    assert!(Session::from_bytes(b"SDMONSES\x01\x00\x00\x00\xff").is_err());
+}
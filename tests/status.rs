@@ -0,0 +1,23 @@
+use sdmon::status::{CardState, CardStatus, APP_CMD, ILLEGAL_COMMAND, READY_FOR_DATA};
+
+#[test]
+fn r1_bits_are_decoded() {
+    let status = CardStatus::new(ILLEGAL_COMMAND | (4 << 9) | READY_FOR_DATA | APP_CMD);
+
+    assert!(status.illegal_command());
+    assert!(status.app_cmd());
+    assert!(!status.com_crc_error());
+    assert!(!status.card_is_locked());
+    assert!(status.has_error());
+    assert_eq!(status.current_state(), CardState::Transfer);
+    assert_eq!(status.flags(), ["ILLEGAL_COMMAND", "READY_FOR_DATA", "APP_CMD"]);
+    assert_eq!(status.to_string(), "0x00400920 tran ILLEGAL_COMMAND READY_FOR_DATA APP_CMD");
+}
+
+#[test]
+fn status_serializes_as_object() {
+    let json = serde_json::to_string(&CardStatus::new(0x900)).unwrap();
+
+    assert_eq!(json, r#"{"raw":"0x00000900","state":"tran","flags":["READY_FOR_DATA"]}"#);
+    assert_eq!(CardStatus::new(0xF << 9).current_state(), CardState::Reserved(15));
+}
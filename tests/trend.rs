@@ -0,0 +1,91 @@
+use sdmon::history::HistoryRecord;
+use sdmon::report::HealthReport;
+use sdmon::trend::{analyze, linear_fit, LifeSource, NoProjection};
+
+const DAY: u64 = 86400;
+const START: u64 = 1_700_000_000;
+
+fn record(day: u64, report: HealthReport) -> HistoryRecord {
+    HistoryRecord { timestamp: START + day * DAY, device: "/dev/mmcblk0".to_string(), serial: Some("0x0000abcd".to_string()), report, raw: Vec::new() }
+}
+
+fn swissbit(avg_cycles: u64) -> HealthReport {
+    let mut report = HealthReport::new("Swissbit", "Swissbit Micron");
+    report.add("User area rated cycles", 3000u32);
+    report.erase_count.avg = Some(avg_cycles);
+    report.set_remaining_life(90.0);
+    report
+}
+
+fn longsys(life: f32, written_tb: f64) -> HealthReport {
+    let mut report = HealthReport::new("Longsys", "Longsys");
+    report.add_with_unit("phyWrGBNum", written_tb, "TB");
+    report.set_remaining_life(life);
+    report
+}
+
+#[test]
+fn fit_recovers_a_line() {
+    let (slope, intercept) = linear_fit(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+    assert!((slope - 2.0).abs() < 1e-9);
+    assert!((intercept - 1.0).abs() < 1e-9);
+    assert_eq!(linear_fit(&[(1.0, 1.0)]), None);
+    assert_eq!(linear_fit(&[(1.0, 1.0), (1.0, 2.0)]), None);
+}
+
+#[test]
+fn rated_cycles_give_the_eol_date() {
+    // 3000 rated cycles, one cycle per day: 90% life on day 0, 1/30 % per day.
+    let records = vec![record(30, swissbit(330)), record(0, swissbit(300)), record(15, swissbit(315))];
+
+    let trend = analyze(&records, 10.0).unwrap();
+
+    assert_eq!(trend.samples, 3);
+    assert_eq!(trend.first, START);
+    assert_eq!(trend.life_source, Some(LifeSource::RatedCycles));
+    assert!((trend.life_percent.unwrap() - 89.0).abs() < 1e-9);
+    assert!((trend.life_per_day.unwrap() - 1.0 / 30.0).abs() < 1e-9);
+    let eol_days = (trend.eol_timestamp.unwrap() - START) as f64 / DAY as f64;
+    assert!((eol_days - 2400.0).abs() < 0.01, "{}", eol_days);
+}
+
+#[test]
+fn reported_life_and_written_data() {
+    let records = vec![record(0, longsys(80.0, 10.0)), record(10, longsys(78.0, 15.0)), record(20, longsys(76.0, 20.0))];
+
+    let trend = analyze(&records, 10.0).unwrap();
+
+    assert_eq!(trend.life_source, Some(LifeSource::Reported));
+    assert!((trend.written_tb_per_day.unwrap() - 0.5).abs() < 1e-9);
+    assert!((trend.life_per_day.unwrap() - 0.2).abs() < 1e-9);
+    // 80% - 0.2% per day reaches 10% after 350 days.
+    assert_eq!(trend.eol_timestamp, Some(START + 350 * DAY));
+}
+
+#[test]
+fn no_wear_means_no_projection() {
+    let records = vec![record(0, longsys(80.0, 10.0)), record(1, longsys(80.0, 10.0))];
+    let trend = analyze(&records, 10.0).unwrap();
+    assert_eq!(trend.eol_timestamp, None);
+
+    let trend = analyze(&records[..1], 10.0).unwrap();
+    assert_eq!(trend.life_per_day, None);
+    assert_eq!(trend.life_percent, Some(80.0));
+
+    assert_eq!(analyze(&[], 10.0), None);
+}
+
+#[test]
+fn missing_projection_gives_the_reason() {
+    let trend = analyze(&[record(0, swissbit(300))], 10.0).unwrap();
+    assert_eq!(trend.no_projection(), Some(NoProjection::TooFewSamples));
+
+    let trend = analyze(&[record(0, HealthReport::new("X", "X")), record(1, HealthReport::new("X", "X"))], 10.0).unwrap();
+    assert_eq!(trend.no_projection(), Some(NoProjection::NoWearMetric));
+
+    let trend = analyze(&[record(0, swissbit(300)), record(1, swissbit(300))], 10.0).unwrap();
+    assert_eq!(trend.no_projection(), Some(NoProjection::NoMeasurableWear));
+
+    let trend = analyze(&[record(0, swissbit(300)), record(1, swissbit(301))], 10.0).unwrap();
+    assert_eq!(trend.no_projection(), None);
+}